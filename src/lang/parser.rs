@@ -1,3 +1,9 @@
+use crate::parse::Spanned;
+
 pub enum Expression {
 
 }
+
+// An expression along with the part of the source it was parsed from (see `SpannedParser`). Subexpressions should be
+// stored as these too, so diagnostics can point at any part of an expression.
+pub type SpannedExpression = Spanned<Expression>;
@@ -1,8 +1,17 @@
-use std::io::{Cursor, SeekFrom};
+use std::any::TypeId;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
 
-use crate::parse::{ParseError, Parser, ParseResult, ReadSeek};
+use crate::parse::{BoxedParser, LineCol, ParseError, Parser, ParseResult, ReadSeek, Span, Spanned};
 use crate::parse;
+use crate::parse::diagnostic;
+use crate::parse::diagnostic::Diagnostic;
 use crate::parse::pos_reader::PositionReader;
+use crate::parse::state;
+use crate::parse::state::MemoKey;
+use crate::parse::std_parsers;
 
 // Parses `first` then `second`, returning the result parsed by both in a tuple.
 pub struct AndParser<P1: Parser, P2: Parser> {
@@ -30,7 +39,82 @@ pub trait AndParserExt: Parser {
 
 impl<P: Parser> AndParserExt for P {}
 
-// Returns the result of `first` if successful, otherwise returning the result of `second`.
+// Tuples of up to 12 parsers are parsers which run each in order, returning all of their results in a flat tuple,
+// so `(a, b, c)` returns `(A, B, C)` where `a.and(b).and(c)` would return `((A, B), C)`. See `seq!` for leaving
+// results out or building a struct from them.
+macro_rules! impl_parser_for_tuple {
+    ($($parser:ident $n:tt),+) => {
+        impl<$($parser: Parser),+> Parser for ($($parser,)+) {
+            type Output = ($($parser::Output,)+);
+
+            fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+                parse::backtrack_on_fail(reader, |r| Ok(($(self.$n.parse(r)?,)+)))
+            }
+        }
+    };
+}
+
+impl_parser_for_tuple!(P0 0);
+impl_parser_for_tuple!(P0 0, P1 1);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9, P10 10);
+impl_parser_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9, P10 10, P11 11);
+
+// Sequences parsers like a tuple does, leaving out the results of those marked with `_:`. For example, this parses
+// `(1, 2)` into `(1, 2)`:
+//
+//   seq!(_: symbol("("), integer::<i32>(), _: symbol(","), integer::<i32>(), _: symbol(")"))
+//
+// Given a struct name and field names instead, the results build the struct directly:
+//
+//   seq!(Point { _: symbol("("), x: integer(), _: symbol(","), y: integer(), _: symbol(")") })
+//
+// Like tuples, this takes up to 12 parsers, including the ones left out.
+#[macro_export]
+macro_rules! seq {
+    ($($name:ident)::+ { $($fields:tt)* }) => {
+        $crate::seq!(@struct [$($name)::+] [] [] [] $($fields)*)
+    };
+    (@struct $name:tt [$($parsers:expr,)*] [$($patterns:tt)*] [$($names:ident)*] _: $parser:expr
+        $(, $($rest:tt)*)?) => {
+        $crate::seq!(@struct $name [$($parsers,)* $parser,] [$($patterns)* _] [$($names)*] $($($rest)*)?)
+    };
+    (@struct $name:tt [$($parsers:expr,)*] [$($patterns:tt)*] [$($names:ident)*] $field:ident: $parser:expr
+        $(, $($rest:tt)*)?) => {
+        $crate::seq!(@struct $name [$($parsers,)* $parser,] [$($patterns)* $field] [$($names)* $field] $($($rest)*)?)
+    };
+    (@struct [$($name:ident)::+] [$($parsers:expr,)*] [$($patterns:tt)*] [$($names:ident)*]) => {
+        $crate::parse::combinators::MapParserExt::map(
+            ($($parsers,)*),
+            |($($patterns,)*)| $($name)::+ { $($names),* },
+        )
+    };
+
+    // Each result kept is bound to `value`, which is a different variable every time since identifiers from different
+    // expansions of a macro don't refer to each other.
+    (@tuple [$($parsers:expr,)*] [$($patterns:tt)*] [$($values:ident)*] _: $parser:expr $(, $($rest:tt)*)?) => {
+        $crate::seq!(@tuple [$($parsers,)* $parser,] [$($patterns)* _] [$($values)*] $($($rest)*)?)
+    };
+    (@tuple [$($parsers:expr,)*] [$($patterns:tt)*] [$($values:ident)*] $parser:expr $(, $($rest:tt)*)?) => {
+        $crate::seq!(@tuple [$($parsers,)* $parser,] [$($patterns)* value] [$($values)* value] $($($rest)*)?)
+    };
+    (@tuple [$($parsers:expr,)*] [$($patterns:tt)*] [$($values:ident)*]) => {
+        $crate::parse::combinators::MapParserExt::map(($($parsers,)*), |($($patterns,)*)| ($($values,)*))
+    };
+    ($($items:tt)+) => {
+        $crate::seq!(@tuple [] [] [] $($items)+)
+    };
+}
+
+// Returns the result of `first` if successful, otherwise returning the result of `second`. If `first` fails with a
+// committed error, `second` is not tried.
 pub struct OrParser<T, P1: Parser<Output=T>, P2: Parser<Output=T>> {
     first: P1,
     second: P2,
@@ -40,7 +124,20 @@ impl<T, P1: Parser<Output=T>, P2: Parser<Output=T>> Parser for OrParser<T, P1, P
     type Output = T;
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
-        parse::backtrack_on_fail(reader, |r| self.first.parse(r).or_else(|_| self.second.parse(r)))
+        parse::backtrack_on_fail(reader, |r| {
+            self.first.parse(r).or_else(|first_error| {
+                if first_error.committed {
+                    return Err(first_error);
+                }
+                match self.second.parse(r) {
+                    Ok(result) => {
+                        state::record_failure(&first_error);
+                        Ok(result)
+                    }
+                    Err(second_error) => Err(first_error.merge(second_error)),
+                }
+            })
+        })
     }
 }
 
@@ -52,6 +149,185 @@ pub trait OrParserExt: Parser {
 
 impl<P: Parser> OrParserExt for P {}
 
+// One of two values, as returned by `OrEitherParser`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+// Like `OrParser`, but `first` and `second` can return different types, returning `Left` with the result of `first` or
+// `Right` with the result of `second`.
+pub struct OrEitherParser<P1: Parser, P2: Parser> {
+    first: P1,
+    second: P2,
+}
+
+impl<P1: Parser, P2: Parser> Parser for OrEitherParser<P1, P2> {
+    type Output = Either<P1::Output, P2::Output>;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let first_error = match self.first.parse(r) {
+                Ok(result) => return Ok(Either::Left(result)),
+                Err(error) if error.committed => return Err(error),
+                Err(error) => error,
+            };
+            match self.second.parse(r) {
+                Ok(result) => {
+                    state::record_failure(&first_error);
+                    Ok(Either::Right(result))
+                }
+                Err(second_error) => Err(first_error.merge(second_error)),
+            }
+        })
+    }
+}
+
+pub trait OrEitherParserExt: Parser {
+    fn or_either<P: Parser>(self, second: P) -> OrEitherParser<Self, P> where Self: Sized {
+        OrEitherParser { first: self, second }
+    }
+}
+
+impl<P: Parser> OrEitherParserExt for P {}
+
+// A list of alternative parsers with the same output type, for `ChoiceParser`. This is implemented for tuples and
+// arrays of up to 12 parsers, and for `Vec`s of parsers.
+pub trait Alternatives {
+    type Output;
+
+    fn count(&self) -> usize;
+
+    // Runs the `n`th parser.
+    fn parse_nth(&self, n: usize, reader: &mut impl ReadSeek) -> ParseResult<Self::Output>;
+}
+
+impl<P: Parser> Alternatives for Vec<P> {
+    type Output = P::Output;
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn parse_nth(&self, n: usize, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        self[n].parse(reader)
+    }
+}
+
+macro_rules! impl_alternatives_for_tuple {
+    ($($parser:ident $n:tt),+) => {
+        impl<T, $($parser: Parser<Output=T>),+> Alternatives for ($($parser,)+) {
+            type Output = T;
+
+            fn count(&self) -> usize {
+                [$($n),+].len()
+            }
+
+            fn parse_nth(&self, n: usize, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+                match n {
+                    $($n => self.$n.parse(reader),)+
+                    _ => panic!("alternative {} out of range", n),
+                }
+            }
+        }
+    };
+}
+
+impl_alternatives_for_tuple!(P0 0);
+impl_alternatives_for_tuple!(P0 0, P1 1);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9, P10 10);
+impl_alternatives_for_tuple!(P0 0, P1 1, P2 2, P3 3, P4 4, P5 5, P6 6, P7 7, P8 8, P9 9, P10 10, P11 11);
+
+macro_rules! impl_alternatives_for_array {
+    ($($len:literal)+) => {
+        $(
+            impl<P: Parser> Alternatives for [P; $len] {
+                type Output = P::Output;
+
+                fn count(&self) -> usize {
+                    $len
+                }
+
+                fn parse_nth(&self, n: usize, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+                    self[n].parse(reader)
+                }
+            }
+        )+
+    };
+}
+
+impl_alternatives_for_array!(1 2 3 4 5 6 7 8 9 10 11 12);
+
+// Tries each of `alternatives` in order, returning the result of the first which succeeds. This is like chaining `or`,
+// but with a flat type, and errors from every alternative are merged the same way. If `longest` is set, every
+// alternative is tried instead, and the one which consumed the most input is used (the first one, if several tie); this
+// is useful for tokens which are prefixes of each other, like `<` and `<=`.
+pub struct ChoiceParser<A: Alternatives> {
+    alternatives: A,
+    longest: bool,
+}
+
+impl<A: Alternatives> Parser for ChoiceParser<A> {
+    type Output = A::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let mut error: Option<ParseError> = None;
+            let mut best = None;
+            for n in 0..self.alternatives.count() {
+                match self.alternatives.parse_nth(n, r) {
+                    Ok(result) if !self.longest => {
+                        best = Some((result, r.offset()?));
+                        break;
+                    }
+                    Ok(result) => {
+                        let end = r.offset()?;
+                        if best.as_ref().is_none_or(|(_, best_end)| end > *best_end) {
+                            best = Some((result, end));
+                        }
+                        r.seek_to(start)?;
+                    }
+                    Err(e) if e.committed => return Err(e),
+                    Err(e) => error = Some(match error {
+                        Some(error) => error.merge(e),
+                        None => e,
+                    }),
+                }
+            }
+
+            match (best, error) {
+                (Some((result, end)), error) => {
+                    if let Some(error) = error {
+                        state::record_failure(&error);
+                    }
+                    r.seek_to(end)?;
+                    Ok(result)
+                }
+                (None, Some(error)) => Err(error),
+                (None, None) => Err(ParseError::new(start, "no alternatives to choose from")),
+            }
+        })
+    }
+}
+
+pub fn choice<A: Alternatives>(alternatives: A) -> ChoiceParser<A> {
+    ChoiceParser { alternatives, longest: false }
+}
+
+pub fn longest_match<A: Alternatives>(alternatives: A) -> ChoiceParser<A> {
+    ChoiceParser { alternatives, longest: true }
+}
+
 // Parses `parser` `times` times, returning all results. One failure causes the entire parse to fail.
 pub struct ExactParser<P: Parser> {
     parser: P,
@@ -80,6 +356,65 @@ pub trait ExactParserExt: Parser {
 
 impl<P: Parser> ExactParserExt for P {}
 
+// Runs `parser` between `min` and `max` times (or any number of times from `min`, if there is no `max`), returning the
+// results in a list. Parsing stops once `max` results are reached, even if more would match, so fixed width fields like
+// two to four hex digits can be parsed with `hex_digit().repeated(2..=4)`. A committed error from `parser` fails the
+// entire parse, as does any failure before `min` results.
+pub struct RepeatParser<P: Parser> {
+    parser: P,
+    min: usize,
+    max: Option<usize>,
+}
+
+impl<P: Parser> Parser for RepeatParser<P> {
+    type Output = Vec<P::Output>;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let mut results = Vec::with_capacity(self.min);
+            while self.max.is_none_or(|max| results.len() < max) {
+                match self.parser.parse(r) {
+                    Ok(result) => results.push(result),
+                    Err(error) if error.committed || results.len() < self.min => return Err(error),
+                    Err(error) => {
+                        state::record_failure(&error);
+                        break;
+                    }
+                }
+            }
+            Ok(results)
+        })
+    }
+}
+
+pub trait RepeatParserExt: Parser {
+    // Parses a number of results in `range`, like `2..=4`, `3..`, or `..5`. Panics if `range` is empty.
+    fn repeated(self, range: impl RangeBounds<usize>) -> RepeatParser<Self> where Self: Sized {
+        let min = match range.start_bound() {
+            Bound::Included(min) => *min,
+            Bound::Excluded(min) => min + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(max) => Some(*max),
+            Bound::Excluded(max) => Some(max.checked_sub(1).expect("empty repetition range")),
+            Bound::Unbounded => None,
+        };
+        assert!(max.is_none_or(|max| min <= max), "empty repetition range");
+        RepeatParser { parser: self, min, max }
+    }
+
+    fn at_least(self, min: usize) -> RepeatParser<Self> where Self: Sized {
+        RepeatParser { parser: self, min, max: None }
+    }
+
+    fn at_most(self, max: usize) -> RepeatParser<Self> where Self: Sized {
+        RepeatParser { parser: self, min: 0, max: Some(max) }
+    }
+}
+
+impl<P: Parser> RepeatParserExt for P {}
+
 // Parses `first` then `second`, returning the result parsed by `second`.
 pub struct ThenParser<P1: Parser, P2: Parser> {
     first: P1,
@@ -92,7 +427,7 @@ impl<P1: Parser, P2: Parser> Parser for ThenParser<P1, P2> {
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
         parse::backtrack_on_fail(reader, |r| {
             self.first.parse(r)?;
-            Ok(self.second.parse(r)?)
+            self.second.parse(r)
         })
     }
 }
@@ -131,7 +466,7 @@ pub trait WithParserExt: Parser {
 
 impl<P: Parser> WithParserExt for P {}
 
-// Runs `parser`, returning its result if successful, returning `None` otherwise.
+// Runs `parser`, returning its result if successful, returning `None` otherwise. Committed errors are not swallowed.
 pub struct OptionalParser<P: Parser> {
     parser: P
 }
@@ -140,7 +475,14 @@ impl<P: Parser> Parser for OptionalParser<P> {
     type Output = Option<P::Output>;
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
-        parse::backtrack_on_fail(reader, |r| Ok(self.parser.parse(r).ok()))
+        parse::backtrack_on_fail(reader, |r| match self.parser.parse(r) {
+            Ok(result) => Ok(Some(result)),
+            Err(error) if error.committed => Err(error),
+            Err(error) => {
+                state::record_failure(&error);
+                Ok(None)
+            }
+        })
     }
 }
 
@@ -152,7 +494,8 @@ pub trait OptionalParserExt: Parser {
 
 impl<P: Parser> OptionalParserExt for P {}
 
-// Runs `parser` zero (one if `min_one` is true) or more times, returning the results in a list.
+// Runs `parser` zero (one if `min_one` is true) or more times, returning the results in a list. A committed error from
+// `parser` fails the entire parse rather than ending the repetition.
 pub struct ManyParser<P: Parser> {
     parser: P,
     min_one: bool,
@@ -164,12 +507,16 @@ impl<P: Parser> Parser for ManyParser<P> {
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
         parse::backtrack_on_fail(reader, |r| {
             let mut results = vec![];
-            let mut parse_result = self.parser.parse(r);
-            while let Ok(result) = parse_result {
-                results.push(result);
-                parse_result = self.parser.parse(r);
+            loop {
+                match self.parser.parse(r) {
+                    Ok(result) => results.push(result),
+                    Err(error) if error.committed || results.is_empty() && self.min_one => return Err(error),
+                    Err(error) => {
+                        state::record_failure(&error);
+                        return Ok(results);
+                    }
+                }
             }
-            if results.is_empty() && self.min_one { Err(parse_result.err().unwrap()) } else { Ok(results) }
         })
     }
 }
@@ -187,6 +534,231 @@ pub trait ManyParserExt: Parser {
 
 impl<P: Parser> ManyParserExt for P {}
 
+// Like `ManyParser`, but combines the results with `fold_fn` as they are parsed, starting from `init`, instead of
+// collecting them into a list. For example, `digit().many_fold(0, |n, d| n * 10 + d.to_digit(10).unwrap())` parses a
+// number without allocating.
+pub struct ManyFoldParser<P: Parser, T: Clone, F: Fn(T, P::Output) -> T> {
+    parser: P,
+    init: T,
+    fold_fn: F,
+}
+
+impl<P: Parser, T: Clone, F: Fn(T, P::Output) -> T> Parser for ManyFoldParser<P, T, F> {
+    type Output = T;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let mut acc = self.init.clone();
+            while let Some(result) = or_none(self.parser.parse(r))? {
+                acc = (self.fold_fn)(acc, result);
+            }
+            Ok(acc)
+        })
+    }
+}
+
+pub trait ManyFoldParserExt: Parser {
+    fn many_fold<T: Clone, F: Fn(T, Self::Output) -> T>(self, init: T, fold_fn: F) -> ManyFoldParser<Self, T, F>
+        where Self: Sized
+    {
+        ManyFoldParser { parser: self, init, fold_fn }
+    }
+}
+
+impl<P: Parser> ManyFoldParserExt for P {}
+
+// Runs `parser` zero (one if `min_one` is true) or more times, with `separator` between each, returning the results in
+// a list. If `trailing` is set, a separator after the last result is consumed too (as in `[1, 2, 3,]`); otherwise, it
+// is left unparsed, so `1, 2,` parses `1, 2` and stops before the last `,`. An empty list only needs no results, so
+// `,` alone parses as an empty list without consuming anything.
+pub struct SepByParser<P: Parser, S: Parser> {
+    parser: P,
+    separator: S,
+    min_one: bool,
+    trailing: bool,
+}
+
+impl<P: Parser, S: Parser> Parser for SepByParser<P, S> {
+    type Output = Vec<P::Output>;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let mut results = vec![];
+            match self.parser.parse(r) {
+                Ok(result) => results.push(result),
+                Err(error) if error.committed || self.min_one => return Err(error),
+                Err(error) => {
+                    state::record_failure(&error);
+                    return Ok(results);
+                }
+            }
+
+            loop {
+                let before_separator = r.offset()?;
+                if or_none(self.separator.parse(r))?.is_none() {
+                    return Ok(results);
+                }
+                match or_none(self.parser.parse(r))? {
+                    Some(result) => results.push(result),
+                    None => {
+                        if !self.trailing {
+                            r.seek_to(before_separator)?;
+                        }
+                        return Ok(results);
+                    }
+                }
+            }
+        })
+    }
+}
+
+pub trait SepByParserExt: Parser {
+    fn sep_by<S: Parser>(self, separator: S) -> SepByParser<Self, S> where Self: Sized {
+        SepByParser { parser: self, separator, min_one: false, trailing: false }
+    }
+
+    // Ensures at least one parse is finished.
+    fn sep_by1<S: Parser>(self, separator: S) -> SepByParser<Self, S> where Self: Sized {
+        SepByParser { parser: self, separator, min_one: true, trailing: false }
+    }
+
+    // Like `sep_by`, but allows a trailing separator.
+    fn sep_end_by<S: Parser>(self, separator: S) -> SepByParser<Self, S> where Self: Sized {
+        SepByParser { parser: self, separator, min_one: false, trailing: true }
+    }
+
+    // Like `sep_by1`, but allows a trailing separator.
+    fn sep_end_by1<S: Parser>(self, separator: S) -> SepByParser<Self, S> where Self: Sized {
+        SepByParser { parser: self, separator, min_one: true, trailing: true }
+    }
+
+    // Parses zero or more results, each followed by `separator`, like statements ending with `;`.
+    fn end_by<S: Parser>(self, separator: S) -> ManyParser<WithParser<Self, S>> where Self: Sized {
+        self.with(separator).many()
+    }
+}
+
+impl<P: Parser> SepByParserExt for P {}
+
+// Parses one or more results of `parser` separated by `op`, combining them with the functions `op` returns. Operators
+// are left associative by default, so `1 - 2 - 3` is `(1 - 2) - 3`, or right associative if `right` is set. An operator
+// without anything after it is left unparsed. For example:
+//
+//   let sub = symbol("-").map(|_| ops::Sub::sub as fn(i32, i32) -> i32);
+//   let expr = integer::<i32>().lexeme().chainl1(sub);
+//
+// For grammars with several levels of operators, see `PrecedenceParser`.
+pub struct ChainParser<P: Parser, O: Parser> {
+    parser: P,
+    op: O,
+    right: bool,
+}
+
+impl<P: Parser, O: Parser> Parser for ChainParser<P, O> where O::Output: Fn(P::Output, P::Output) -> P::Output {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let mut operands = vec![self.parser.parse(r)?];
+            let mut ops = vec![];
+            loop {
+                let before_op = r.offset()?;
+                let op = match or_none(self.op.parse(r))? {
+                    Some(op) => op,
+                    None => break,
+                };
+                match or_none(self.parser.parse(r))? {
+                    Some(operand) => {
+                        operands.push(operand);
+                        ops.push(op);
+                    }
+                    None => {
+                        r.seek_to(before_op)?;
+                        break;
+                    }
+                }
+            }
+
+            if self.right {
+                let mut result = operands.pop().unwrap();
+                for (op, left) in ops.into_iter().rev().zip(operands.into_iter().rev()) {
+                    result = op(left, result);
+                }
+                Ok(result)
+            } else {
+                let mut operands = operands.into_iter();
+                let first = operands.next().unwrap();
+                Ok(ops.into_iter().zip(operands).fold(first, |left, (op, right)| op(left, right)))
+            }
+        })
+    }
+}
+
+pub trait ChainParserExt: Parser {
+    fn chainl1<O: Parser>(self, op: O) -> ChainParser<Self, O> where Self: Sized {
+        ChainParser { parser: self, op, right: false }
+    }
+
+    fn chainr1<O: Parser>(self, op: O) -> ChainParser<Self, O> where Self: Sized {
+        ChainParser { parser: self, op, right: true }
+    }
+}
+
+impl<P: Parser> ChainParserExt for P {}
+
+// Runs `parser` zero or more times until `end` succeeds, returning the results in a list. `end` is tried before each
+// parse and is consumed, so `string_char.many_till("\"")` parses the rest of a string literal, closing quote included.
+// If neither `end` nor `parser` succeeds, the error says either was expected.
+pub struct ManyTillParser<P: Parser, E: Parser> {
+    parser: P,
+    end: E,
+}
+
+impl<P: Parser, E: Parser> Parser for ManyTillParser<P, E> {
+    type Output = Vec<P::Output>;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let mut results = vec![];
+            loop {
+                let end_error = match self.end.parse(r) {
+                    Ok(_) => return Ok(results),
+                    Err(error) if error.committed => return Err(error),
+                    Err(error) => error,
+                };
+                match self.parser.parse(r) {
+                    Ok(result) => {
+                        state::record_failure(&end_error);
+                        results.push(result);
+                    }
+                    Err(error) if error.committed => return Err(error),
+                    Err(error) => return Err(end_error.merge(error)),
+                }
+            }
+        })
+    }
+}
+
+pub trait ManyTillParserExt: Parser {
+    fn many_till<E: Parser>(self, end: E) -> ManyTillParser<Self, E> where Self: Sized {
+        ManyTillParser { parser: self, end }
+    }
+}
+
+impl<P: Parser> ManyTillParserExt for P {}
+
+// Turns an ordinary failure into `None` (recording it in case it was the furthest one), passing committed errors on.
+fn or_none<T>(result: ParseResult<T>) -> ParseResult<Option<T>> {
+    match result {
+        Ok(result) => Ok(Some(result)),
+        Err(error) if error.committed => Err(error),
+        Err(error) => {
+            state::record_failure(&error);
+            Ok(None)
+        }
+    }
+}
+
 // Runs `prefix`, `parser`, and `suffix` in order, returning the result of `parser` if all are successful.
 pub struct BetweenParser<P1: Parser, P2: Parser, P3: Parser> {
     prefix: P1,
@@ -237,93 +809,1140 @@ pub trait MapParserExt<U, F: Fn(Self::Output) -> U>: Parser {
 
 impl<U, P: Parser, F: Fn(P::Output) -> U> MapParserExt<U, F> for P {}
 
-// Parser which wraps another parser. This is useful when writing parsers for grammars with mutually recursive rules,
-// since this type contains only the output type `T`, avoiding the problem of infinitely expanding types.
-pub struct MutualRecursionParser<'a, T> {
-    func: Box<dyn Fn(&mut dyn ReadSeek) -> ParseResult<T> + 'a>,
+// Like `MapParser`, but `mapping_fn` can reject the result by returning an error, which fails the parse with the
+// error's message at the input the result was parsed from. This is for checks which can't be made while parsing, like
+// `integer::<i64>().try_map(u8::try_from)` rejecting integers too large for a `u8`.
+pub struct TryMapParser<P: Parser, F> {
+    parser: P,
+    mapping_fn: F,
 }
 
-impl<'a, T> MutualRecursionParser<'a, T> {
-    pub fn new(parser: impl Parser<Output=T> + 'a) -> Self {
-        MutualRecursionParser {
-            func: box move |reader| {
-                // This is rather inefficient but I can't be bothered to think of a better solution at the moment.
-                let mut buf = Cursor::new(vec![]);
-                let current = reader.stream_position()?;
-                reader.read_to_end(buf.get_mut())?;
-
-                // Parse then seek the original reader to the correct position, taking into account backtracking and
-                // the result of the parse.
-                let result = parser.parse(&mut buf);
-                let seek_offset = if result.is_err() { current } else { current + buf.position() };
-                reader.seek(SeekFrom::Start(seek_offset))?;
-                result
-            },
-        }
+impl<U, E: Display, P: Parser, F: Fn(P::Output) -> Result<U, E>> Parser for TryMapParser<P, F> {
+    type Output = U;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let result = self.parser.parse(r)?;
+            let span = Span::new(start, r.offset()?);
+            (self.mapping_fn)(result).map_err(|e| ParseError::new(start, &e.to_string()).with_span(span))
+        })
     }
 }
 
-impl<'a, T> Parser for MutualRecursionParser<'a, T> {
-    type Output = T;
+pub trait TryMapParserExt: Parser {
+    fn try_map<U, E: Display, F>(self, mapping_fn: F) -> TryMapParser<Self, F>
+        where Self: Sized, F: Fn(Self::Output) -> Result<U, E>
+    {
+        TryMapParser { parser: self, mapping_fn }
+    }
+}
 
-    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
-        (self.func)(reader)
+impl<P: Parser> TryMapParserExt for P {}
+
+// Runs `parser`, then builds a parser from its result with `parser_fn` and runs that, returning its result. This is for
+// input whose later parts depend on what was parsed earlier, like a length prefixed block:
+//
+//   let block = integer::<usize>().with(":").flat_map(|len| any_char().exact(len));
+pub struct FlatMapParser<P: Parser, F> {
+    parser: P,
+    parser_fn: F,
+}
+
+impl<P: Parser, Q: Parser, F: Fn(P::Output) -> Q> Parser for FlatMapParser<P, F> {
+    type Output = Q::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let result = self.parser.parse(r)?;
+            (self.parser_fn)(result).parse(r)
+        })
     }
 }
 
-pub trait MutualRecursionParserExt: Parser {
-    fn recursive<'a>(self) -> MutualRecursionParser<'a, Self::Output> where Self: Sized + 'a {
-        MutualRecursionParser::new(self)
+pub trait FlatMapParserExt: Parser {
+    fn flat_map<Q: Parser, F: Fn(Self::Output) -> Q>(self, parser_fn: F) -> FlatMapParser<Self, F> where Self: Sized {
+        FlatMapParser { parser: self, parser_fn }
     }
 }
 
-impl<P: Parser> MutualRecursionParserExt for P {}
+impl<P: Parser> FlatMapParserExt for P {}
 
-// Wraps a parser with a position tracking reader, adding detail (line/column number, line content, error location) to
-// error messages.
-pub struct PositionTrackingParser<P: Parser> {
+// Runs `parser`, failing with `message` at the input the result was parsed from if `predicate` rejects the result.
+pub struct VerifyParser<P: Parser, F: Fn(&P::Output) -> bool> {
     parser: P,
+    predicate: F,
+    message: String,
 }
 
-impl<P: Parser> PositionTrackingParser<P> {
-    pub fn new(parser: P) -> Self {
-        PositionTrackingParser { parser }
+impl<P: Parser, F: Fn(&P::Output) -> bool> Parser for VerifyParser<P, F> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let result = self.parser.parse(r)?;
+            if (self.predicate)(&result) {
+                return Ok(result);
+            }
+            Err(ParseError::new(start, &self.message).with_span(Span::new(start, r.offset()?)))
+        })
+    }
+}
+
+pub trait VerifyParserExt: Parser {
+    fn verify<F: Fn(&Self::Output) -> bool>(self, predicate: F, message: &str) -> VerifyParser<Self, F>
+        where Self: Sized
+    {
+        VerifyParser { parser: self, predicate, message: message.to_string() }
     }
+}
 
-    fn parse_internal(&self, reader: &mut impl ReadSeek, to_end: bool) -> ParseResult<P::Output> {
-        let mut reader = PositionReader::new(reader).ok_or(ParseError::new("reader is not at start of stream"))?;
-        let result = if to_end { self.parser.parse_to_end(&mut reader) } else { self.parser.parse(&mut reader) };
+impl<P: Parser> VerifyParserExt for P {}
 
-        result.map_err(|ParseError { reason }| {
-            let line = reader.current_line().unwrap();
-            let col = reader.col() + 1;
+// Runs `parser`, describing what it expects as `name` in errors. This only replaces what was expected where `parser`
+// started, so `expr.label("expression")` on `)` fails with "expected expression, found ')'" instead of listing every
+// token an expression can start with, but errors from further into an expression are still reported as they are.
+pub struct LabelParser<P: Parser> {
+    parser: P,
+    name: String,
+}
+
+impl<P: Parser> LabelParser<P> {
+    fn relabel(&self, start: u64, error: ParseError) -> ParseError {
+        if error.span.start == start && !error.expected.is_empty() {
+            error.with_expected(vec![self.name.clone()])
+        } else {
+            error
+        }
+    }
+}
 
-            let position_part = format!("error ({}:{}):", reader.line() + 1, col);
-            let line_padding = " ".repeat(position_part.len() + 1);
-            let cursor_padding = " ".repeat(position_part.len() + col as usize);
+impl<P: Parser> Parser for LabelParser<P> {
+    type Output = P::Output;
 
-            let message = format!("{} {}\n{}{}\n{}^", position_part, reason, line_padding, line, cursor_padding);
-            ParseError::new(&message)
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let (result, furthest) = state::track_failures(|| self.parser.parse(r));
+            if let Some(furthest) = furthest {
+                state::record_failure(&self.relabel(start, furthest));
+            }
+            result.map_err(|error| self.relabel(start, error))
         })
     }
 }
 
-impl<P: Parser> Parser for PositionTrackingParser<P> {
+pub trait LabelParserExt: Parser {
+    fn label(self, name: &str) -> LabelParser<Self> where Self: Sized {
+        LabelParser { parser: self, name: name.to_string() }
+    }
+}
+
+impl<P: Parser> LabelParserExt for P {}
+
+// Marks a commit point: once `parser` starts running, its failures are committed, so enclosing alternatives are not
+// tried and the error is reported as is. Placing this after a distinguishing prefix, as in `"fn".then(body.cut())`,
+// means a malformed body is reported as such instead of the parse falling through to other alternatives.
+pub struct CutParser<P: Parser> {
+    parser: P,
+}
+
+impl<P: Parser> Parser for CutParser<P> {
     type Output = P::Output;
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
-        self.parse_internal(reader, false)
+        let (result, furthest) = state::track_failures(|| parse::backtrack_on_fail(reader, |r| self.parser.parse(r)));
+        match (result, furthest) {
+            // Failures swallowed inside `parser` are merged in, since nothing outside of it will get the chance to.
+            (Err(error), Some(furthest)) if !error.committed => Err(furthest.merge(error).with_committed(true)),
+            (Err(error), _) => Err(error.with_committed(true)),
+            (result, furthest) => {
+                if let Some(furthest) = furthest {
+                    state::record_failure(&furthest);
+                }
+                result
+            }
+        }
     }
+}
 
-    fn parse_to_end(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
-        self.parse_internal(reader, true)
+pub trait CutParserExt: Parser {
+    fn cut(self) -> CutParser<Self> where Self: Sized {
+        CutParser { parser: self }
     }
 }
 
-pub trait PositionTrackingParserExt: Parser {
-    fn with_position(self) -> PositionTrackingParser<Self> where Self: Sized {
-        PositionTrackingParser::new(self)
+impl<P: Parser> CutParserExt for P {}
+
+// Runs `parser`, turning any committed error into an ordinary one so that enclosing alternatives are tried again. This
+// is the opt-in for backtracking out of a `CutParser`.
+pub struct AttemptParser<P: Parser> {
+    parser: P,
+}
+
+impl<P: Parser> Parser for AttemptParser<P> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| self.parser.parse(r).map_err(|error| error.with_committed(false)))
     }
 }
 
-impl<P: Parser> PositionTrackingParserExt for P {}
+pub trait AttemptParserExt: Parser {
+    fn attempt(self) -> AttemptParser<Self> where Self: Sized {
+        AttemptParser { parser: self }
+    }
+}
+
+impl<P: Parser> AttemptParserExt for P {}
+
+// Runs `parser` and returns its result without consuming any input, so the input can be checked before deciding how to
+// parse it. Failures are returned as is.
+pub struct PeekParser<P: Parser> {
+    parser: P,
+}
+
+impl<P: Parser> Parser for PeekParser<P> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let result = self.parser.parse(r)?;
+            r.seek_to(start)?;
+            Ok(result)
+        })
+    }
+}
+
+pub trait PeekParserExt: Parser {
+    fn peek(self) -> PeekParser<Self> where Self: Sized {
+        PeekParser { parser: self }
+    }
+}
+
+impl<P: Parser> PeekParserExt for P {}
+
+// Parses `parser`, then checks that `lookahead` matches the input after it without consuming that input. For example,
+// `integer().followed_by(one_of(",)"))` parses an integer only if it's followed by a `,` or `)`.
+pub struct FollowedByParser<P: Parser, L: Parser> {
+    parser: P,
+    lookahead: L,
+}
+
+impl<P: Parser, L: Parser> Parser for FollowedByParser<P, L> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let result = self.parser.parse(r)?;
+            let end = r.offset()?;
+            self.lookahead.parse(r)?;
+            r.seek_to(end)?;
+            Ok(result)
+        })
+    }
+}
+
+pub trait FollowedByParserExt: Parser {
+    fn followed_by<L: Parser>(self, lookahead: L) -> FollowedByParser<Self, L> where Self: Sized {
+        FollowedByParser { parser: self, lookahead }
+    }
+}
+
+impl<P: Parser> FollowedByParserExt for P {}
+
+// Parses `parser`, then checks that `lookahead` does not match the input after it, without consuming any input past
+// `parser`. This tells keywords apart from identifiers which start with them: `"if".not_followed_by(alphanumeric())`
+// parses the `if` in `if x`, but fails on `iffy` with "unexpected 'f'", naming the input which must not appear there.
+// Failures inside `lookahead` are expected, so they aren't reported as the furthest failure.
+pub struct NotFollowedByParser<P: Parser, L: Parser> {
+    parser: P,
+    lookahead: L,
+}
+
+impl<P: Parser, L: Parser> Parser for NotFollowedByParser<P, L> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let result = self.parser.parse(r)?;
+            let end = r.offset()?;
+            match state::track_failures(|| self.lookahead.parse(r)).0 {
+                Err(error) if error.cause.is_some() => Err(error),
+                Err(_) => Ok(result),
+                Ok(_) => {
+                    let found = r.offset()?;
+                    let mut bytes = vec![0; (found - end) as usize];
+                    r.seek_to(end)?;
+                    r.read_exact(&mut bytes).map_err(|e| ParseError::io(end, e))?;
+                    let message = format!("unexpected {}", std_parsers::describe_bytes(&bytes));
+                    Err(ParseError::new(end, &message).with_span(Span::new(end, found)))
+                }
+            }
+        })
+    }
+}
+
+pub trait NotFollowedByParserExt: Parser {
+    fn not_followed_by<L: Parser>(self, lookahead: L) -> NotFollowedByParser<Self, L> where Self: Sized {
+        NotFollowedByParser { parser: self, lookahead }
+    }
+}
+
+impl<P: Parser> NotFollowedByParserExt for P {}
+
+// Runs `parser`, recovering from failure by skipping input up to and including the next match of `sync` (or to eof if
+// there is none) and returning `error_node(error)` in its place. The error is recorded in the current session, so
+// `Parser::parse_to_end_recovering` can report every error in the input in one run. For example, a statement parser
+// could use `statement.recover(";", |e| Statement::Error(e))` to carry on with the next statement.
+//
+// If recovering wouldn't consume any input, the error is returned as is, so repeating this can't loop forever.
+pub struct RecoverParser<P: Parser, S: Parser, F: Fn(ParseError) -> P::Output> {
+    parser: P,
+    sync: S,
+    error_node: F,
+}
+
+impl<P: Parser, S: Parser, F: Fn(ParseError) -> P::Output> Parser for RecoverParser<P, S, F> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        let start = reader.offset()?;
+        let (result, furthest) = state::track_failures(|| self.parser.parse(reader));
+        let error = match (result, furthest) {
+            (Err(error), Some(furthest)) if !error.committed => furthest.merge(error),
+            (Err(error), _) => error,
+            (result, furthest) => {
+                if let Some(furthest) = furthest {
+                    state::record_failure(&furthest);
+                }
+                return result;
+            }
+        };
+
+        // Failures while looking for `sync` don't say anything about the input, so they aren't recorded.
+        state::track_failures(|| -> ParseResult<()> {
+            while self.sync.parse(reader).is_err() && reader.read_byte()?.is_some() {}
+            Ok(())
+        }).0?;
+
+        if reader.offset()? == start {
+            return Err(error);
+        }
+        state::record_recovered(error.clone());
+        Ok((self.error_node)(error))
+    }
+}
+
+pub trait RecoverParserExt: Parser {
+    fn recover<S, F>(self, sync: S, error_node: F) -> RecoverParser<Self, S, F>
+        where Self: Sized,
+              S: Parser,
+              F: Fn(ParseError) -> Self::Output
+    {
+        RecoverParser { parser: self, sync, error_node }
+    }
+}
+
+impl<P: Parser> RecoverParserExt for P {}
+
+// Caches the results of `parser` by starting position for the rest of the current session (see `state::session`), so
+// it runs at most once at each position. Backtracking into a memoized rule at a position it was already tried at then
+// reuses the earlier result instead of parsing the same input again. If every rule of a grammar is memoized and no rule
+// does more than a bounded amount of work outside of the rules it calls, parsing takes linear time.
+//
+// Results are keyed by the type `K`, which identifies the rule. `memoize` uses the type of the parser itself, so it
+// only works on zero sized parsers, like grammar rules written as functions (`expr.memoize()`). A rule which builds a
+// new parser on every call, like `fn expr() -> impl Parser { ... .memoize() }`, would otherwise never find the results
+// cached by the others, so other parsers are memoized with `memoize_as`, which takes the function the rule is written
+// as, like `left_recursive` does.
+pub struct MemoParser<K, P: Parser> {
+    parser: P,
+    key: PhantomData<K>,
+}
+
+#[derive(Clone)]
+struct MemoEntry<T> {
+    result: ParseResult<T>,
+    end: u64,
+    furthest: Option<ParseError>,
+    recovered: Vec<ParseError>,
+}
+
+impl<K: 'static, P: Parser> Parser for MemoParser<K, P> where P::Output: Clone + 'static {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        let key = (TypeId::of::<K>(), 0, reader.offset()?);
+        let entry = match state::memo_get::<MemoEntry<P::Output>>(&key) {
+            Some(entry) => entry,
+            None => {
+                let recovered_count = state::recovered_count();
+                let (result, furthest) = state::track_failures(|| self.parser.parse(reader));
+                let recovered = state::recovered_since(recovered_count);
+                let entry = MemoEntry { result, end: reader.offset()?, furthest, recovered };
+                state::memo_insert(key, entry.clone());
+                entry
+            }
+        };
+
+        if let Some(furthest) = &entry.furthest {
+            state::record_failure(furthest);
+        }
+        for error in entry.recovered {
+            state::record_recovered(error);
+        }
+        reader.seek_to(entry.end)?;
+        entry.result
+    }
+}
+
+pub trait MemoParserExt: Parser {
+    fn memoize(self) -> MemoParser<Self, Self> where Self: Sized {
+        const { assert!(mem::size_of::<Self>() == 0, "only zero sized parsers can be memoized, use `memoize_as`") };
+        MemoParser { parser: self, key: PhantomData }
+    }
+
+    fn memoize_as<K>(self, _rule: K) -> MemoParser<K, Self> where Self: Sized {
+        MemoParser { parser: self, key: PhantomData }
+    }
+}
+
+impl<P: Parser> MemoParserExt for P {}
+
+// Runs `parser`, which is the body of the left recursive grammar rule `rule`, using the seed growing technique: the
+// recursive call is first made to fail, so the body falls back to a non-recursive alternative. That result is then
+// memoized and the body is run again, with the recursive call now returning the previous result, for as long as the
+// body keeps consuming more input. For example:
+//
+//   // expr = expr "+" term | term
+//   fn expr() -> impl Parser<Output=i32> {
+//       expr.recursive().with("+").and(term).map(|(a, b)| a + b).or(term).left_recursive(expr)
+//   }
+//
+// `rule` is only used to identify the rule (by type, as the results are memoized), so it should be the function the
+// rule is written as. Indirectly left recursive rules work too, as long as only one of the rules in the cycle is marked
+// and the rest aren't memoized.
+pub struct LeftRecursiveParser<R, P: Parser> {
+    parser: P,
+    rule: PhantomData<R>,
+}
+
+impl<R: 'static, P: Parser> Parser for LeftRecursiveParser<R, P> where P::Output: Clone + 'static {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        // Growing the seed relies on memoization, so there has to be a session to store the results in.
+        if !state::in_session() {
+            return state::session(|| self.parse(reader));
+        }
+
+        let start = reader.offset()?;
+        let key = (TypeId::of::<R>(), usize::MAX, start);
+        if let Some(entry) = state::memo_get::<MemoEntry<P::Output>>(&key) {
+            reader.seek_to(entry.end)?;
+            return entry.result;
+        }
+
+        // The seed error says nothing, so it doesn't show up in the error reported if the rule fails entirely.
+        let seed_error = ParseError::new(start, "").with_message(None);
+        let seed = MemoEntry::<P::Output> { result: Err(seed_error), end: start, furthest: None, recovered: vec![] };
+        state::memo_insert(key, seed);
+
+        reader.mark(start);
+        let best = self.grow_seed(reader, key, start);
+        reader.release(start);
+
+        let best = best?;
+        state::memo_insert(key, best.clone());
+        reader.seek_to(best.end)?;
+        best.result
+    }
+}
+
+impl<R, P: Parser> LeftRecursiveParser<R, P> where P::Output: Clone + 'static {
+    // Runs the body repeatedly from `start`, returning the result which consumed the most input.
+    fn grow_seed(&self, reader: &mut impl ReadSeek, key: MemoKey, start: u64) -> ParseResult<MemoEntry<P::Output>> {
+        let result = self.parser.parse(reader);
+        let mut best = MemoEntry { result, end: reader.offset()?, furthest: None, recovered: vec![] };
+        while best.result.is_ok() {
+            state::memo_insert(key, best.clone());
+            reader.seek_to(start)?;
+
+            let result = self.parser.parse(reader);
+            let end = reader.offset()?;
+            if result.is_err() || end <= best.end {
+                break;
+            }
+            best = MemoEntry { result, end, furthest: None, recovered: vec![] };
+        }
+        Ok(best)
+    }
+}
+
+pub trait LeftRecursiveParserExt: Parser {
+    fn left_recursive<R>(self, _rule: R) -> LeftRecursiveParser<R, Self> where Self: Sized {
+        LeftRecursiveParser { parser: self, rule: PhantomData }
+    }
+}
+
+impl<P: Parser> LeftRecursiveParserExt for P {}
+
+// Runs `parser`, wrapping its result in a `Spanned` with the location of the input it was parsed from.
+pub struct SpannedParser<P: Parser> {
+    parser: P,
+}
+
+impl<P: Parser> Parser for SpannedParser<P> {
+    type Output = Spanned<P::Output>;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let (start_pos, start) = (r.offset()?, r.line_col());
+            let value = self.parser.parse(r)?;
+            let (end_pos, end) = (r.offset()?, r.line_col());
+            Ok(Spanned { value, span: Span::new(start_pos, end_pos), start, end })
+        })
+    }
+}
+
+pub trait SpannedParserExt: Parser {
+    fn spanned(self) -> SpannedParser<Self> where Self: Sized {
+        SpannedParser { parser: self }
+    }
+}
+
+impl<P: Parser> SpannedParserExt for P {}
+
+// Runs `parser`, returning the input it consumed rather than its output. The input is read again to get it, which
+// works with any reader; when parsing from a `SliceReader`, its `recognize` method borrows the input instead.
+pub struct RecognizeParser<P: Parser> {
+    parser: P,
+}
+
+impl<P: Parser> Parser for RecognizeParser<P> {
+    type Output = String;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            self.parser.parse(r)?;
+            let end = r.offset()?;
+
+            r.seek_to(start)?;
+            let mut consumed = Vec::with_capacity((end - start) as usize);
+            while r.offset()? < end {
+                match r.read_byte()? {
+                    Some(byte) => consumed.push(byte),
+                    _ => break,
+                }
+            }
+            String::from_utf8(consumed).map_err(|_| {
+                ParseError::new(start, "consumed input is not valid UTF-8").with_span(Span::new(start, end))
+            })
+        })
+    }
+}
+
+pub trait RecognizeParserExt: Parser {
+    fn recognize(self) -> RecognizeParser<Self> where Self: Sized {
+        RecognizeParser { parser: self }
+    }
+}
+
+impl<P: Parser> RecognizeParserExt for P {}
+
+// Parser which wraps another parser. This is useful when writing parsers for grammars with mutually recursive rules,
+// since this type contains only the output type `T`, avoiding the problem of infinitely expanding types. The wrapped
+// parser runs directly on the caller's reader through dynamic dispatch.
+pub struct MutualRecursionParser<'a, T> {
+    parser: BoxedParser<'a, T>,
+}
+
+impl<'a, T> MutualRecursionParser<'a, T> {
+    pub fn new(parser: impl Parser<Output=T> + 'a) -> Self {
+        MutualRecursionParser { parser: parser.boxed() }
+    }
+}
+
+impl<'a, T> Parser for MutualRecursionParser<'a, T> {
+    type Output = T;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
+        self.parser.parse(reader)
+    }
+}
+
+pub trait MutualRecursionParserExt: Parser {
+    fn recursive<'a>(self) -> MutualRecursionParser<'a, Self::Output> where Self: Sized + 'a {
+        MutualRecursionParser::new(self)
+    }
+}
+
+impl<P: Parser> MutualRecursionParserExt for P {}
+
+pub trait BoxedParserExt: Parser {
+    // Erases the type of this parser, which is useful for storing parsers in collections or choosing them at runtime.
+    fn boxed<'a>(self) -> BoxedParser<'a, Self::Output> where Self: Sized + 'a {
+        Box::new(self)
+    }
+}
+
+impl<P: Parser> BoxedParserExt for P {}
+
+// Wraps a parser with a position tracking reader, so that `spanned` results include line and column numbers, and
+// renders errors as diagnostics (see `Diagnostic`) showing the lines they happened on. The rendered diagnostic is
+// stored in the error's `rendered` field, with ANSI colour if `color` is set, and the rest of the error is left as it
+// is. Positions are counted from wherever the reader is when parsing starts, which is at line and column `base`.
+//
+// Rendering needs to read the input again, so errors from readers which can't seek back to where parsing started (like
+// `StreamReader`) are left as they are.
+pub struct PositionTrackingParser<P: Parser> {
+    parser: P,
+    color: bool,
+    base: LineCol,
+}
+
+impl<P: Parser> PositionTrackingParser<P> {
+    pub fn new(parser: P) -> Self {
+        PositionTrackingParser { parser, color: false, base: LineCol { line: 1, col: 1 } }
+    }
+
+    pub fn with_color(self, color: bool) -> Self {
+        PositionTrackingParser { color, ..self }
+    }
+
+    pub fn with_base(self, base: LineCol) -> Self {
+        PositionTrackingParser { base, ..self }
+    }
+
+    fn parse_internal(&self, reader: &mut impl ReadSeek, to_end: bool) -> ParseResult<P::Output> {
+        let start = reader.offset()?;
+        let result = {
+            let mut reader = PositionReader::new(reader)?.with_base(self.base);
+            state::session(|| {
+                if to_end { self.parser.parse_to_end(&mut reader) } else { self.parser.parse(&mut reader) }
+            })
+        };
+        result.map_err(|error| self.describe_errors(reader, start, vec![error]).remove(0))
+    }
+
+    // Renders the diagnostic for each error.
+    fn describe_errors(&self, reader: &mut impl ReadSeek, start: u64, errors: Vec<ParseError>) -> Vec<ParseError> {
+        let source = match diagnostic::read_source(reader, start) {
+            Ok(source) => source,
+            Err(_) => return errors,
+        };
+        errors.into_iter().map(|error| {
+            let rendered = Diagnostic::from_error(&error).render_at(&source, start, self.base, self.color);
+            error.with_rendered(rendered)
+        }).collect()
+    }
+}
+
+impl<P: Parser> Parser for PositionTrackingParser<P> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        self.parse_internal(reader, false)
+    }
+
+    fn parse_to_end(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
+        self.parse_internal(reader, true)
+    }
+
+    fn parse_to_end_recovering(&self, reader: &mut impl ReadSeek) -> (Option<Self::Output>, Vec<ParseError>)
+        where Self: Sized
+    {
+        let mut position_reader = match PositionReader::new(reader) {
+            Ok(position_reader) => position_reader.with_base(self.base),
+            Err(error) => return (None, vec![error]),
+        };
+        let start = position_reader.position();
+        let (result, errors) = self.parser.parse_to_end_recovering(&mut position_reader);
+        (result, self.describe_errors(reader, start, errors))
+    }
+}
+
+pub trait PositionTrackingParserExt: Parser {
+    fn with_position(self) -> PositionTrackingParser<Self> where Self: Sized {
+        PositionTrackingParser::new(self)
+    }
+}
+
+impl<P: Parser> PositionTrackingParserExt for P {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::TryFrom;
+    use std::io::Cursor;
+
+    use crate::parse::{BoxedParser, LineCol, Parser, ParseResult, ReadSeek, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::lexeme::*;
+    use crate::parse::numeric::integer;
+    use crate::parse::pos_reader::PositionReader;
+    use crate::parse::slice_reader::SliceReader;
+    use crate::parse::std_parsers::*;
+
+    fn cursor(input: &str) -> Cursor<&[u8]> {
+        Cursor::new(input.as_bytes())
+    }
+
+    #[test]
+    fn cut_stops_later_alternatives() {
+        let error = "fn".then("(".cut()).or("fnord").parse(&mut cursor("fnord")).unwrap_err();
+        assert!(error.committed);
+        assert_eq!(error.span.start, 2);
+        assert_eq!(error.expected, ["'('"]);
+    }
+
+    #[test]
+    fn attempt_allows_backtracking_out_of_cut() {
+        let result = "fn".then("(".cut()).attempt().or("fnord").parse(&mut cursor("fnord"));
+        assert_eq!(result.unwrap(), "fnord");
+    }
+
+    #[test]
+    fn committed_errors_end_many_and_optional() {
+        let error = "a".then("b".cut()).many().parse(&mut cursor("abac")).unwrap_err();
+        assert_eq!(error.span.start, 3);
+        assert!("a".then("b".cut()).optional().parse(&mut cursor("ac")).is_err());
+        assert!("a".then("b".cut()).optional().parse(&mut cursor("c")).unwrap().is_none());
+    }
+
+    fn nested() -> impl Parser<Output=usize> {
+        nested.recursive().between("(", ")").optional().map(|depth| depth.map_or(0, |depth| depth + 1))
+    }
+
+    #[test]
+    fn recursive_parses_in_place() {
+        let mut reader = cursor("((()))rest");
+        assert_eq!(nested().parse(&mut reader).unwrap(), 3);
+        assert_eq!(reader.position(), 6);
+    }
+
+    #[test]
+    fn recursive_errors_have_absolute_positions() {
+        let error = nested().parse_to_end(&mut cursor("((x))")).unwrap_err();
+        assert_eq!(error.span.start, 2);
+        assert_eq!(error.expected, ["'('", "')'"]);
+    }
+
+    #[test]
+    fn boxed_parsers_can_be_chosen_at_runtime() {
+        let keywords: Vec<BoxedParser<&str>> = vec!["let".boxed(), "fn".boxed(), "if".map(|_| "if!").boxed()];
+        let mut reader = cursor("fnif");
+        let parsed = keywords.iter().map(|keyword| keyword.parse(&mut reader).ok()).collect::<Vec<_>>();
+        assert_eq!(parsed, [None, Some("fn"), Some("if!")]);
+        assert_eq!(reader.position(), 4);
+    }
+
+    // Gets the address of the reader it's given, looking through any references and boxes around it.
+    struct ReaderAddress;
+
+    impl Parser for ReaderAddress {
+        type Output = usize;
+
+        fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+            Ok(reader.as_dyn() as *mut dyn ReadSeek as *mut u8 as usize)
+        }
+    }
+
+    #[test]
+    fn boxed_parsers_pass_the_reader_through_unchanged() {
+        let mut parser = ReaderAddress.boxed();
+        for _ in 0..100 {
+            parser = parser.boxed();
+        }
+        let mut reader = cursor("");
+        let address = &mut reader as *mut Cursor<&[u8]> as usize;
+        assert_eq!(parser.parse(&mut reader).unwrap(), address);
+    }
+
+    #[test]
+    fn recursive_parses_deeply_nested_input() {
+        let input = "(".repeat(1000) + &")".repeat(1000);
+        let mut reader = cursor(&input);
+        assert_eq!(nested().parse(&mut reader).unwrap(), 1000);
+        assert_eq!(reader.position(), 2000);
+    }
+
+    thread_local! {
+        static RUNS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Parses an "a", counting how many times it ran on this thread.
+    struct CountedA;
+
+    impl Parser for CountedA {
+        type Output = char;
+
+        fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+            RUNS.with(|runs| runs.set(runs.get() + 1));
+            "a".map(|_| 'a').parse(reader)
+        }
+    }
+
+    #[test]
+    fn memoized_rule_runs_once_per_position() {
+        let parser = CountedA.memoize().with("b").or(CountedA.memoize().with("c"));
+        assert_eq!(parser.parse_to_end(&mut cursor("ac")).unwrap(), 'a');
+        assert_eq!(RUNS.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn memoized_results_only_last_for_a_session() {
+        let parser = CountedA.memoize().with("b").or(CountedA.memoize().with("c"));
+        assert!(parser.parse(&mut cursor("ac")).is_ok());
+        assert!(parser.parse_to_end(&mut cursor("ac")).is_ok());
+        assert_eq!(RUNS.with(Cell::get), 3);
+    }
+
+    #[test]
+    fn recognize_returns_consumed_input() {
+        let mut reader = cursor("12_345x");
+        assert_eq!(digit().or(one_of("_")).many().recognize().parse(&mut reader).unwrap(), "12_345");
+        assert_eq!(reader.position(), 6);
+
+        let mut reader = cursor("12x");
+        assert_eq!(digit().many().then("y").recognize().parse(&mut reader).unwrap_err().span.start, 2);
+        assert_eq!(reader.position(), 0);
+    }
+
+    // Parses an "a" with a parser which isn't zero sized, like most rules written as functions.
+    fn counted_rule() -> impl Parser<Output=char> {
+        CountedA.with("").memoize_as(counted_rule)
+    }
+
+    #[test]
+    fn memoized_rules_share_results_between_calls() {
+        let parser = counted_rule.with("b").or(counted_rule.with("c"));
+        assert_eq!(parser.parse_to_end(&mut cursor("ac")).unwrap(), 'a');
+        assert_eq!(RUNS.with(Cell::get), 1);
+    }
+
+    // chain = "a" chain "b" | "a" chain "c" | ""
+    fn chain() -> impl Parser<Output=usize> {
+        let nested = || CountedA.then(chain.recursive()).map(|depth| depth + 1);
+        nested().with("b").or(nested().with("c")).optional().map(|depth| depth.unwrap_or(0)).memoize_as(chain)
+    }
+
+    #[test]
+    fn memoized_rules_take_linear_time() {
+        for depth in [10, 20, 40] {
+            RUNS.with(|runs| runs.set(0));
+            let input = "a".repeat(depth) + &"c".repeat(depth);
+            assert_eq!(chain().parse_to_end(&mut cursor(&input)).unwrap(), depth);
+            // Each alternative runs once at every position, including the end of the "a"s.
+            assert_eq!(RUNS.with(Cell::get), 2 * (depth + 1));
+        }
+    }
+
+    // sub = sub "-" number | number
+    fn sub() -> impl Parser<Output=i32> {
+        let number = non_neg_decimal::<i32>;
+        sub.recursive().with("-").and(number).map(|(a, b)| a - b).or(number).left_recursive(sub)
+    }
+
+    #[test]
+    fn left_recursion_associates_to_the_left() {
+        assert_eq!(sub().parse_to_end(&mut cursor("10-3-2")).unwrap(), 5);
+        assert_eq!(sub().parse_to_end(&mut cursor("7")).unwrap(), 7);
+    }
+
+    #[test]
+    fn left_recursion_stops_before_incomplete_operation() {
+        let mut reader = cursor("8-2-");
+        assert_eq!(sub().parse(&mut reader).unwrap(), 6);
+        assert_eq!(reader.position(), 3);
+        assert_eq!(sub().parse_to_end(&mut cursor("8-2-")).unwrap_err().span.start, 4);
+    }
+
+    fn statements() -> impl Parser<Output=Vec<bool>> {
+        "a".with(";").map(|_| true).recover(";", |_| false).many()
+    }
+
+    #[test]
+    fn recovery_reports_every_error() {
+        let (result, errors) = statements().parse_to_end_recovering(&mut cursor("a;x;a;yy;a;"));
+        assert_eq!(result.unwrap(), [true, false, true, false, true]);
+        assert_eq!(errors.iter().map(|error| error.span.start).collect::<Vec<_>>(), [2, 6]);
+    }
+
+    #[test]
+    fn parse_to_end_fails_with_first_recovered_error() {
+        let error = statements().parse_to_end(&mut cursor("a;x;a;y;")).unwrap_err();
+        assert_eq!(error.span.start, 2);
+        assert_eq!(error.expected, ["'a'"]);
+    }
+
+    #[test]
+    fn recovery_without_progress_fails() {
+        let (result, errors) = "a".recover(";", |_| "").parse_to_end_recovering(&mut cursor(""));
+        assert!(result.is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn spanned_includes_line_and_column_from_position_reader() {
+        let mut input = cursor("ab\n  cd");
+        let mut reader = PositionReader::new(&mut input).unwrap();
+        let spanned = "ab".then("\n  ").then("cd".spanned()).parse(&mut reader).unwrap();
+        assert_eq!(spanned.value, "cd");
+        assert_eq!(spanned.span, Span::new(5, 7));
+        assert_eq!(spanned.start, Some(LineCol { line: 2, col: 3 }));
+        assert_eq!(spanned.end, Some(LineCol { line: 2, col: 5 }));
+    }
+
+    #[test]
+    fn spanned_without_line_tracking_has_only_offsets() {
+        let spanned = "a".then("bc".spanned()).parse(&mut cursor("abc")).unwrap().map(str::len);
+        assert_eq!((spanned.value, spanned.span, spanned.start), (2, Span::new(1, 3), None));
+    }
+
+
+
+    #[test]
+    fn sep_by_leaves_a_trailing_separator() {
+        assert_eq!(integer::<i32>().sep_by(",").parse_to_end(&mut cursor("1,2,3")).unwrap(), [1, 2, 3]);
+        assert!(integer::<i32>().sep_by(",").parse_to_end(&mut cursor("")).unwrap().is_empty());
+
+        let mut reader = cursor("1,2,");
+        assert_eq!(integer::<i32>().sep_by(",").parse(&mut reader).unwrap(), [1, 2]);
+        assert_eq!(reader.position(), 3);
+        let error = integer::<i32>().sep_by(",").parse_to_end(&mut cursor("1,2,")).unwrap_err();
+        assert_eq!(error.span.start, 4);
+    }
+
+    #[test]
+    fn sep_end_by_and_end_by() {
+        assert_eq!(integer::<i32>().sep_end_by(",").parse_to_end(&mut cursor("1,2,")).unwrap(), [1, 2]);
+        assert_eq!(integer::<i32>().sep_end_by(",").parse_to_end(&mut cursor("1,2")).unwrap(), [1, 2]);
+        assert!(integer::<i32>().sep_by1(",").parse_to_end(&mut cursor("")).is_err());
+        assert!(integer::<i32>().sep_end_by1(",").parse_to_end(&mut cursor(",")).is_err());
+        assert_eq!(integer::<i32>().end_by(";").parse_to_end(&mut cursor("1;2;")).unwrap(), [1, 2]);
+        assert!(integer::<i32>().end_by(";").parse_to_end(&mut cursor("1;2")).is_err());
+    }
+
+    fn minus() -> impl Parser<Output=fn(i32, i32) -> i32> {
+        symbol("-").map(|_| std::ops::Sub::sub as fn(i32, i32) -> i32)
+    }
+
+    #[test]
+    fn chains_associate_as_named() {
+        let number = || integer::<i32>().with_sign(false).lexeme();
+        assert_eq!(number().chainl1(minus()).parse_to_end(&mut cursor("10 - 2 - 3")).unwrap(), 5);
+        assert_eq!(number().chainr1(minus()).parse_to_end(&mut cursor("10 - 2 - 3")).unwrap(), 11);
+
+        let mut reader = cursor("10 - 2 -");
+        assert_eq!(number().chainl1(minus()).parse(&mut reader).unwrap(), 8);
+        assert_eq!(reader.position(), 7);
+    }
+
+    #[test]
+    fn many_till_stops_at_end() {
+        let mut reader = cursor("ab*/c");
+        assert_eq!(any_char().many_till("*/").parse(&mut reader).unwrap(), ['a', 'b']);
+        assert_eq!(reader.position(), 4);
+        let error = digit().many_till("]").parse_to_end(&mut cursor("12x")).unwrap_err();
+        assert_eq!(error.span.start, 2);
+        assert_eq!(error.expected, ["']'", "digit"]);
+    }
+
+    #[test]
+    fn repeated_takes_as_many_as_allowed() {
+        let mut reader = cursor("abcdef1");
+        assert_eq!(hex_digit().repeated(2..=4).parse(&mut reader).unwrap(), ['a', 'b', 'c', 'd']);
+        assert_eq!(hex_digit().repeated(2..=4).parse(&mut reader).unwrap(), ['e', 'f', '1']);
+        assert!(hex_digit().repeated(2..=4).parse(&mut cursor("a")).is_err());
+        assert_eq!(hex_digit().repeated(..).parse(&mut cursor("abc")).unwrap().len(), 3);
+        assert_eq!(hex_digit().repeated(..2).parse(&mut cursor("abc")).unwrap().len(), 1);
+        assert_eq!(hex_digit().at_least(2).parse(&mut cursor("abc")).unwrap().len(), 3);
+        assert_eq!(hex_digit().at_most(2).parse(&mut cursor("abc")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn repeated_failing_early_consumes_nothing() {
+        let mut reader = cursor("ab");
+        let error = hex_digit().repeated(3..).parse(&mut reader).unwrap_err();
+        assert_eq!(error.span.start, 2);
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn repeated_works_on_string_parsers() {
+        let mut reader = cursor("ababa");
+        assert_eq!("ab".repeated(1..).parse(&mut reader).unwrap(), ["ab", "ab"]);
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn repeated_rejects_empty_ranges() {
+        digit().repeated(3..3);
+    }
+
+    #[test]
+    fn many_fold_accumulates_without_a_list() {
+        let number = digit().many_fold(0, |n, digit| n * 10 + digit.to_digit(10).unwrap());
+        assert_eq!(number.parse_to_end(&mut cursor("1234")).unwrap(), 1234);
+        assert_eq!(number.parse_to_end(&mut cursor("")).unwrap(), 0);
+    }
+
+    #[test]
+    fn peek_does_not_consume() {
+        let mut reader = cursor("abc");
+        assert_eq!(any_char().peek().parse(&mut reader).unwrap(), 'a');
+        assert_eq!(reader.position(), 0);
+        assert!(digit().peek().parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn followed_by_leaves_the_lookahead() {
+        let mut reader = cursor("12,");
+        assert_eq!(integer::<i32>().followed_by(one_of(",)")).parse(&mut reader).unwrap(), 12);
+        assert_eq!(reader.position(), 2);
+        let error = integer::<i32>().followed_by(one_of(",)")).parse(&mut cursor("12;")).unwrap_err();
+        assert_eq!(error.span.start, 2);
+    }
+
+    #[test]
+    fn not_followed_by_tells_keywords_from_identifiers() {
+        let keyword = || "if".not_followed_by(alphanumeric());
+        assert!(keyword().parse(&mut cursor("if x")).is_ok());
+        assert!(keyword().parse_to_end(&mut cursor("if")).is_ok());
+
+        let mut reader = cursor("iffy");
+        let error = keyword().parse(&mut reader).unwrap_err();
+        assert_eq!(error.reason(), "unexpected 'f'");
+        assert_eq!(error.span, Span::new(2, 3));
+        assert_eq!(reader.position(), 0);
+        assert_eq!(keyword().parse_to_end(&mut cursor("iffy")).unwrap_err().reason(), "unexpected 'f'");
+    }
+
+    #[test]
+    fn try_map_reports_conversion_errors_over_the_input() {
+        let byte = || integer::<i64>().try_map(u8::try_from);
+        assert_eq!(byte().parse_to_end(&mut cursor("200")).unwrap(), 200);
+        let error = byte().parse(&mut cursor("300")).unwrap_err();
+        assert_eq!(error.reason(), "out of range integral type conversion attempted");
+        assert_eq!(error.span, Span::new(0, 3));
+    }
+
+    #[test]
+    fn verify_rejects_results() {
+        let even = integer::<i32>().verify(|n| n % 2 == 0, "expected an even number");
+        assert_eq!(even.parse_to_end(&mut cursor("4")).unwrap(), 4);
+        let mut reader = cursor("3");
+        assert_eq!(even.parse(&mut reader).unwrap_err().reason(), "expected an even number");
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn label_replaces_expected_only_where_parser_started() {
+        let number = || integer::<i32>().with_sign(false).label("number");
+        assert_eq!(number().parse_to_end(&mut cursor("x")).unwrap_err().reason(), "expected number");
+        let error = number().or("(".map(|_| 0)).parse_to_end(&mut cursor("x")).unwrap_err();
+        assert_eq!(error.reason(), "expected number or '(', found 'x'");
+        let error = number().parse_to_end(&mut cursor("0x")).unwrap_err();
+        assert_eq!(error.reason(), "expected hexadecimal digit");
+    }
+
+    #[test]
+    fn flat_map_chooses_the_next_parser_from_a_result() {
+        let block = || integer::<usize>().with(":").flat_map(|len| any_char().exact(len));
+        let mut reader = cursor("3:abcd");
+        assert_eq!(block().parse(&mut reader).unwrap(), ['a', 'b', 'c']);
+        assert_eq!(reader.position(), 5);
+
+        let mut reader = cursor("5:abc");
+        assert_eq!(block().parse(&mut reader).unwrap_err().span.start, 5);
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn flat_map_can_match_a_delimiter_it_parsed() {
+        let quoted = any_char().flat_map(|quote| none_of(&quote.to_string()).many().with(satisfy(move |c| c == quote)));
+        assert_eq!(quoted.parse_to_end(&mut cursor("#a'b#")).unwrap(), ['a', '\'', 'b']);
+        assert!(quoted.parse_to_end(&mut cursor("'ab#")).is_err());
+    }
+
+    #[test]
+    fn choice_takes_the_first_alternative_which_parses() {
+        let letters = choice(("a", "b", "c", "d", "e"));
+        assert_eq!(letters.parse_to_end(&mut cursor("d")).unwrap(), "d");
+        let error = letters.parse_to_end(&mut cursor("x")).unwrap_err();
+        assert_eq!(error.expected, ["'a'", "'b'", "'c'", "'d'", "'e'"]);
+
+        let mut reader = cursor("<=");
+        assert_eq!(choice(["<", "<=", "="]).parse(&mut reader).unwrap(), "<");
+        assert_eq!(choice(vec!["x", "y"]).parse_to_end(&mut cursor("y")).unwrap(), "y");
+        assert!(choice(Vec::<&str>::new()).parse(&mut cursor("y")).is_err());
+    }
+
+    #[test]
+    fn longest_match_takes_the_alternative_which_consumed_most() {
+        let ops = longest_match(["<", "<=", "="]);
+        assert_eq!(ops.parse_to_end(&mut cursor("<=")).unwrap(), "<=");
+        let mut reader = cursor("<x");
+        assert_eq!(ops.parse(&mut reader).unwrap(), "<");
+        assert_eq!(reader.position(), 1);
+    }
+
+    #[test]
+    fn or_either_keeps_both_output_types() {
+        let parser = integer::<i32>().or_either(alpha());
+        assert_eq!(parser.parse_to_end(&mut cursor("5")).unwrap(), Either::Left(5));
+        assert_eq!(parser.parse_to_end(&mut cursor("q")).unwrap(), Either::Right('q'));
+        assert_eq!(parser.parse_to_end(&mut cursor("#")).unwrap_err().expected, ["integer", "letter"]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[test]
+    fn tuples_parse_in_sequence() {
+        let parser = (alpha(), digit(), alpha());
+        assert_eq!(parser.parse_to_end(&mut cursor("a1b")).unwrap(), ('a', '1', 'b'));
+        let mut reader = cursor("a1!");
+        assert!(parser.parse(&mut reader).is_err());
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn seq_drops_underscored_results() {
+        let number = || integer::<i32>().lexeme();
+        let pair = crate::seq!(_: symbol("("), number(), _: symbol(","), number(), _: symbol(")"));
+        assert_eq!(pair.parse_to_end(&mut cursor("( 1 , 2 )")).unwrap(), (1, 2));
+        assert_eq!(crate::seq!(digit()).parse_to_end(&mut cursor("1")).unwrap(), ('1',));
+        assert_eq!(crate::seq!(digit(), digit(),).parse_to_end(&mut cursor("12")).unwrap(), ('1', '2'));
+    }
+
+    #[test]
+    fn seq_builds_structs() {
+        let number = || integer::<i32>().lexeme();
+        let point = crate::seq!(Point { _: symbol("("), x: number(), _: symbol(","), y: number(), _: symbol(")"), });
+        assert_eq!(point.parse_to_end(&mut cursor("(1, 2)")).unwrap(), Point { x: 1, y: 2 });
+        let point = crate::seq!(self::Point { x: integer(), _: ",", y: integer() });
+        assert_eq!(point.parse_to_end(&mut cursor("3,4")).unwrap(), Point { x: 3, y: 4 });
+    }
+
+    #[test]
+    fn position_tracking_counts_from_base() {
+        let mut reader = SliceReader::from("ab\ncd!");
+        reader.seek_to(3).unwrap();
+        let parser = "cd".then("?").with_position().with_base(LineCol { line: 2, col: 1 });
+        let error = parser.parse(&mut reader).unwrap_err();
+        let rendered = error.rendered.as_ref().unwrap();
+        assert!(rendered.contains(" --> 2:3\n"));
+        assert!(rendered.contains("2 | cd!\n"));
+        assert_eq!(error.expected, vec!["'?'"]);
+    }
+}
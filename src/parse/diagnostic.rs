@@ -0,0 +1,401 @@
+use std::cmp;
+
+use crate::parse::{LineCol, ParseError, ParseResult, ReadSeek, Span};
+
+// How serious a diagnostic is, which decides the word it starts with and its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+// A span of the input to point out in a diagnostic. The primary label is where the problem is, and is underlined with
+// `^`; secondary labels give context (like where an unclosed delimiter was opened), and are underlined with `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+    pub primary: bool,
+}
+
+impl Label {
+    pub fn primary(span: Span, message: Option<&str>) -> Self {
+        Label { span, message: message.map(str::to_string), primary: true }
+    }
+
+    pub fn secondary(span: Span, message: &str) -> Self {
+        Label { span, message: Some(message.to_string()), primary: false }
+    }
+}
+
+// A message about the input which can be rendered along with the lines it refers to, like:
+//
+//   error: non-associative operators cannot be chained
+//    --> 1:8
+//     |
+//   1 | 1 == 2 == 3
+//     |        ^^
+//     |   -- previous operator
+//     |
+//     = help: add parentheses to make the grouping explicit
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Self {
+        Diagnostic { severity, message: message.to_string(), labels: vec![], notes: vec![], help: vec![] }
+    }
+
+    // Creates a diagnostic pointing at where `error` happened, along with its labels, notes, and help.
+    pub fn from_error(error: &ParseError) -> Self {
+        let mut labels = vec![Label::primary(error.span, None)];
+        labels.extend(error.labels.iter().cloned());
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.reason(),
+            labels,
+            notes: error.notes.clone(),
+            help: error.help.clone(),
+        }
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    // Renders this diagnostic with the lines of `source` its labels cover, using ANSI escape codes if `color` is set.
+    pub fn render(&self, source: &[u8], color: bool) -> String {
+        self.render_at(source, 0, LineCol { line: 1, col: 1 }, color)
+    }
+
+    // Like `render`, but for when `source` is only part of the input, starting at the position `start` which is on line
+    // and column `base`.
+    pub fn render_at(&self, source: &[u8], start: u64, base: LineCol, color: bool) -> String {
+        let painter = Painter { color };
+        let lines = Lines { start, base, ..Lines::new(source) };
+        let labels = self.labels.iter().map(|label| (label, lines.locate(label.span))).collect::<Vec<_>>();
+
+        // Show every line a label starts or ends on, along with any single line between two of them.
+        let mut shown = labels.iter().flat_map(|(_, (start, end))| vec![start.0, end.0]).collect::<Vec<_>>();
+        shown.sort_unstable();
+        shown.dedup();
+        let width = shown.last().map_or(1, |line| lines.number(*line).to_string().len());
+
+        let severity = painter.paint(self.severity.color(), self.severity.name());
+        let mut out = format!("{}{}\n", severity, painter.paint(BOLD, &format!(": {}", self.message)));
+        let location = labels.iter().find(|(label, _)| label.primary).or_else(|| labels.first());
+        if let Some((_, ((line, col), _))) = location {
+            let (line, col) = (lines.number(*line), lines.column(*line, *col));
+            out += &format!("{}{} {}:{}\n", " ".repeat(width), painter.paint(BLUE, "-->"), line, col);
+        }
+        if !shown.is_empty() {
+            out += &format!("{}\n", painter.gutter("", width));
+        }
+
+        let mut previous = None;
+        for &line in &shown {
+            match previous {
+                Some(previous) if line == previous + 2 => {
+                    out += &self.render_line(&lines, line - 1, &labels, width, painter);
+                }
+                Some(previous) if line > previous + 2 => out += &format!("{}\n", painter.paint(BLUE, "...")),
+                _ => {}
+            }
+            out += &self.render_line(&lines, line, &labels, width, painter);
+            previous = Some(line);
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() {
+            out += &format!("{}\n", painter.gutter("", width));
+        }
+        let notes = self.notes.iter().map(|note| ("note", note));
+        for (kind, text) in notes.chain(self.help.iter().map(|help| ("help", help))) {
+            let kind = painter.paint(BOLD, &format!("{}:", kind));
+            out += &format!("{} {} {} {}\n", " ".repeat(width), painter.paint(BLUE, "="), kind, text);
+        }
+        out.pop();
+        out
+    }
+
+    // Renders `line` followed by a row underlining the part of it covered by each label on it.
+    fn render_line(&self, lines: &Lines, line: usize, labels: &[(&Label, Location)], width: usize, painter: Painter)
+        -> String
+    {
+        let mut out = format!("{} {}", painter.gutter(&lines.number(line).to_string(), width), lines.text(line));
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        let content = lines.line(line);
+
+        let mut covering = labels.iter().filter(|(_, (start, end))| (start.0..=end.0).contains(&line))
+            .collect::<Vec<_>>();
+        covering.sort_by_key(|(label, (start, _))| (!label.primary, if start.0 == line { start.1 } else { 0 }));
+        for (label, (start, end)) in covering {
+            // Spans can start or end on the `\r` of a CRLF line ending, which `content` doesn't include.
+            let from = cmp::min(if start.0 == line { start.1 } else { 0 }, content.len());
+            let to = if end.0 == line { cmp::min(cmp::max(end.1, from), content.len()) } else { content.len() };
+
+            // Keep tabs in the padding so the underline lines up with the text above it.
+            let padding = display_units(&String::from_utf8_lossy(&content[..from]))
+                .into_iter()
+                .map(|(unit, width)| if unit == "\t" { unit.to_string() } else { " ".repeat(width) })
+                .collect::<String>();
+            let marked = String::from_utf8_lossy(&content[from..to]);
+            let marked = cmp::max(display_units(&marked).iter().map(|(_, width)| width).sum(), 1);
+
+            let (mark, style) = if label.primary { ("^", self.severity.color()) } else { ("-", BLUE) };
+            let mut underline = mark.repeat(marked);
+            if let (Some(message), true) = (&label.message, end.0 == line) {
+                underline = format!("{} {}", underline, message);
+            }
+            out += &format!("{} {}{}\n", painter.gutter("", width), padding, painter.paint(style, &underline));
+        }
+        out
+    }
+}
+
+// Splits `text` into the units which take up columns when displayed, along with the number of columns each takes up.
+// With the `grapheme-width` feature, these are grapheme clusters measured by their display width, so wide characters
+// (like CJK) take up two columns and combining sequences one; otherwise, they are characters, each taking up one.
+#[cfg(feature = "grapheme-width")]
+fn display_units(text: &str) -> Vec<(&str, usize)> {
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthStr;
+
+    text.graphemes(true).map(|grapheme| (grapheme, grapheme.width())).collect()
+}
+
+#[cfg(not(feature = "grapheme-width"))]
+fn display_units(text: &str) -> Vec<(&str, usize)> {
+    text.char_indices().map(|(i, char)| (&text[i..i + char.len_utf8()], 1)).collect()
+}
+
+// The line and column (both from 0, with the column in bytes) of the start and end of a span.
+type Location = ((usize, usize), (usize, usize));
+
+#[derive(Clone, Copy)]
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(self, style: &str, text: &str) -> String {
+        if self.color { format!("{}{}{}", style, text, RESET) } else { text.to_string() }
+    }
+
+    // Renders the part of a line before its content, which is either the line number or blank.
+    fn gutter(self, number: &str, width: usize) -> String {
+        self.paint(BLUE, &format!("{:>width$} |", number, width = width))
+    }
+}
+
+// The input split into lines, for finding where a span is and getting the lines it covers.
+struct Lines<'a> {
+    source: &'a [u8],
+    starts: Vec<usize>,
+
+    // The position of the start of `source` in the input, and its line and column number.
+    start: u64,
+    base: LineCol,
+}
+
+impl<'a> Lines<'a> {
+    fn new(source: &'a [u8]) -> Self {
+        let newlines = source.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| i + 1);
+        let starts = std::iter::once(0).chain(newlines).collect();
+        Lines { source, starts, start: 0, base: LineCol { line: 1, col: 1 } }
+    }
+
+    // Gets the line number to show for `line`.
+    fn number(&self, line: usize) -> u64 {
+        self.base.line + line as u64
+    }
+
+    // Gets the column number to show for the position `col` bytes into `line`, which counts characters. A position on
+    // the `\r` of a CRLF line ending is shown as the end of the line.
+    fn column(&self, line: usize, col: usize) -> u64 {
+        let content = self.line(line);
+        let chars = String::from_utf8_lossy(&content[..cmp::min(col, content.len())]).chars().count() as u64;
+        if line == 0 { self.base.col + chars } else { chars + 1 }
+    }
+
+    // Gets the location of `span`. A span ending at the start of a line (like one covering a whole line, including the
+    // newline) is treated as ending at the end of the line before it.
+    fn locate(&self, span: Span) -> Location {
+        let start = self.position(span.start.saturating_sub(self.start) as usize);
+        let mut end = self.position(cmp::max(span.start, span.end).saturating_sub(self.start) as usize);
+        if end.1 == 0 && end.0 > start.0 {
+            end = (end.0 - 1, self.line(end.0 - 1).len());
+        }
+        (start, end)
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = cmp::min(offset, self.source.len());
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        (line, offset - self.starts[line])
+    }
+
+    // Gets the content of `line`, without its newline.
+    fn line(&self, line: usize) -> &'a [u8] {
+        let end = self.starts.get(line + 1).map_or(self.source.len(), |next| next - 1);
+        let line = &self.source[self.starts[line]..end];
+        line.strip_suffix(b"\r").unwrap_or(line)
+    }
+
+    fn text(&self, line: usize) -> String {
+        String::from_utf8_lossy(self.line(line)).into_owned()
+    }
+}
+
+// Reads `reader` from `start` to the end so that diagnostics can be rendered against it, leaving its position
+// unchanged.
+pub fn read_source(reader: &mut impl ReadSeek, start: u64) -> ParseResult<Vec<u8>> {
+    let pos = reader.offset()?;
+    reader.seek_to(start)?;
+    let mut source = vec![];
+    reader.read_to_end(&mut source).map_err(|e| ParseError::io(start, e))?;
+    reader.seek_to(pos)?;
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Seek, SeekFrom};
+
+    use crate::parse::{IoReader, ParseError, Parser, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::diagnostic::{self, Diagnostic, Label, Severity};
+    use crate::parse::slice_reader::SliceReader;
+    use crate::parse::std_parsers::*;
+
+    #[test]
+    fn renders_labels_notes_and_help() {
+        let error = ParseError::new(7, "non-associative operators cannot be chained")
+            .with_span(Span::new(7, 9))
+            .with_label(Span::new(2, 4), "previous operator")
+            .with_help("add parentheses to make the grouping explicit");
+        let expected = [
+            "error: non-associative operators cannot be chained",
+            " --> 1:8",
+            "  |",
+            "1 | 1 == 2 == 3",
+            "  |        ^^",
+            "  |   -- previous operator",
+            "  |",
+            "  = help: add parentheses to make the grouping explicit",
+        ];
+        assert_eq!(error.render(b"1 == 2 == 3", false), expected.join("\n"));
+    }
+
+    #[test]
+    fn renders_spans_covering_several_lines() {
+        let diagnostic = Diagnostic::new(Severity::Warning, "unclosed")
+            .with_label(Label::primary(Span::new(2, 11), Some("this block")))
+            .with_note("blocks need a closing brace");
+        let expected = [
+            "warning: unclosed",
+            " --> 1:3",
+            "  |",
+            "1 | a {",
+            "  |   ^",
+            "2 |   b",
+            "  | ^^^",
+            "3 | c",
+            "  | ^ this block",
+            "  |",
+            "  = note: blocks need a closing brace",
+        ];
+        assert_eq!(diagnostic.render(b"a {\n  b\nc\n", false), expected.join("\n"));
+    }
+
+    #[test]
+    fn colors_are_optional() {
+        let error = ParseError::expected(0, "digit").with_found("'x'");
+        assert!(error.render(b"x", true).starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(!error.render(b"x", false).contains('\x1b'));
+    }
+
+    #[test]
+    fn renders_spans_on_crlf_line_endings() {
+        let error = ParseError::expected(1, "'b'").with_found("'\\r'").with_span(Span::new(1, 2));
+        assert_eq!(error.render(b"a\r\nc", false), "error: expected 'b', found '\\r'\n --> 1:2\n  |\n1 | a\n  |  ^");
+
+        let error = ParseError::new(0, "bad").with_span(Span::new(0, 4)).with_label(Span::new(1, 2), "here");
+        assert_eq!(error.render(b"ab\r\ncd\r\n", false), "error: bad\n --> 1:1\n  |\n1 | ab\n  | ^^\n  |  - here");
+    }
+
+    #[test]
+    fn positions_errors_after_crlf_line_endings() {
+        let error = "a\r".then("b").with_position().parse_to_end(&mut SliceReader::from("a\r\nc")).unwrap_err();
+        assert!(error.rendered.as_ref().unwrap().contains(" --> 1:2"));
+        assert_eq!(error.expected, vec!["'b'"]);
+        assert_eq!(error.span, Span::new(2, 3));
+
+        let parser = alpha().then(one_of(" \t\r").many()).then(";").with_position();
+        let result = parser.parse_to_end(&mut SliceReader::from("x\r\n;"));
+        assert!(result.unwrap_err().rendered.as_ref().unwrap().contains(" --> 1:2"));
+    }
+
+    // Seeks anywhere, but fails to read.
+    struct Unreadable;
+
+    impl Read for Unreadable {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("unreadable"))
+        }
+    }
+
+    impl Seek for Unreadable {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn source_read_errors_are_at_the_start() {
+        let error = diagnostic::read_source(&mut IoReader::new(Unreadable), 4).unwrap_err();
+        assert_eq!(error.span.start, 4);
+    }
+}
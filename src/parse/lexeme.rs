@@ -0,0 +1,169 @@
+use crate::parse;
+use crate::parse::{ParseError, Parser, ParseResult, ReadSeek, Span};
+use crate::parse::std_parsers;
+
+// Parses and discards trivia: whitespace, along with line comments starting with `line_comment` and block comments
+// between `block_comment`'s delimiters, if they are set. Block comments can contain other block comments if `nested`
+// is set, like `/* a /* b */ c */`. This never fails unless a block comment is left unterminated.
+#[derive(Debug, Clone, Copy)]
+pub struct Trivia {
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    nested: bool,
+}
+
+impl Trivia {
+    // Creates a skipper for whitespace only.
+    pub fn whitespace() -> Self {
+        Trivia { line_comment: None, block_comment: None, nested: false }
+    }
+
+    pub fn with_line_comment(self, start: &'static str) -> Self {
+        Trivia { line_comment: Some(start), ..self }
+    }
+
+    pub fn with_block_comment(self, start: &'static str, end: &'static str) -> Self {
+        Trivia { block_comment: Some((start, end)), ..self }
+    }
+
+    pub fn with_nested(self, nested: bool) -> Self {
+        Trivia { nested, ..self }
+    }
+
+    // Skips the rest of a block comment which started at `start`.
+    fn skip_block_comment(&self, reader: &mut impl ReadSeek, start: u64) -> ParseResult<()> {
+        let (open, close) = self.block_comment.unwrap();
+        let mut depth = 1;
+        while depth > 0 {
+            if parse::eat(reader, close)? {
+                depth -= 1;
+            } else if self.nested && parse::eat(reader, open)? {
+                depth += 1;
+            } else if reader.read_byte()?.is_none() {
+                let error = ParseError::new(start, "unterminated block comment")
+                    .with_span(Span::new(start, start + open.len() as u64))
+                    .with_label(Span::at(reader.offset()?), "input ends here");
+                return Err(error.with_committed(true));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Parser for Trivia {
+    type Output = ();
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| loop {
+            let before = r.offset()?;
+            while let Ok(Some(char)) = r.peek_char() {
+                if !char.is_whitespace() {
+                    break;
+                }
+                r.read_char()?;
+            }
+
+            if let Some(start) = self.line_comment {
+                if parse::eat(r, start)? {
+                    while !matches!(r.peek_byte()?, Some(b'\n') | None) {
+                        r.read_byte()?;
+                    }
+                }
+            }
+            if let Some((open, _)) = self.block_comment {
+                let start = r.offset()?;
+                if parse::eat(r, open)? {
+                    self.skip_block_comment(r, start)?;
+                }
+            }
+
+            if r.offset()? == before {
+                return Ok(());
+            }
+        })
+    }
+}
+
+// Creates a skipper for whitespace, `//` line comments, and nested `/* */` block comments. This is what `lexeme` and
+// `symbol` skip; use `Trivia` to skip something else.
+pub fn trivia() -> Trivia {
+    Trivia::whitespace().with_line_comment("//").with_block_comment("/*", "*/").with_nested(true)
+}
+
+// Parses `parser`, then skips any trivia after it. Making each token of a grammar a lexeme means whitespace and
+// comments are allowed between any two tokens; to allow them at the start of the input too, parse `trivia` first.
+pub struct LexemeParser<P: Parser> {
+    parser: P,
+    trivia: Trivia,
+}
+
+impl<P: Parser> Parser for LexemeParser<P> {
+    type Output = P::Output;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let result = self.parser.parse(r)?;
+            self.trivia.parse(r)?;
+            Ok(result)
+        })
+    }
+}
+
+pub trait LexemeParserExt: Parser {
+    fn lexeme(self) -> LexemeParser<Self> where Self: Sized {
+        LexemeParser { parser: self, trivia: trivia() }
+    }
+
+    fn lexeme_with(self, trivia: Trivia) -> LexemeParser<Self> where Self: Sized {
+        LexemeParser { parser: self, trivia }
+    }
+}
+
+impl<P: Parser> LexemeParserExt for P {}
+
+// Parses `symbol` as a lexeme, like an operator or keyword.
+pub fn symbol(symbol: &str) -> impl Parser<Output=&str> + '_ {
+    std_parsers::string(symbol).lexeme()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{Parser, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::lexeme::*;
+    use crate::parse::slice_reader::SliceReader;
+    use crate::parse::std_parsers::*;
+
+    #[test]
+    fn trivia_skips_whitespace_and_comments() {
+        let mut reader = SliceReader::from(" \t\n// c\n /* a /* b */ c */ x");
+        trivia().parse(&mut reader).unwrap();
+        assert_eq!(reader.rest(), b"x");
+    }
+
+    #[test]
+    fn block_comments_nest_only_if_asked_to() {
+        let mut reader = SliceReader::from("/* a /* b */ x */");
+        Trivia::whitespace().with_block_comment("/*", "*/").parse(&mut reader).unwrap();
+        assert_eq!(reader.rest(), b"x */");
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_committed() {
+        let error = trivia().parse(&mut SliceReader::from("  /* a /* b */ x")).unwrap_err();
+        assert_eq!(error.reason(), "unterminated block comment");
+        assert_eq!(error.span, Span::new(2, 4));
+        assert!(error.committed);
+    }
+
+    #[test]
+    fn lexemes_skip_trailing_trivia() {
+        let parser = symbol("a").then(symbol("b"));
+        assert_eq!(parser.parse_to_end(&mut SliceReader::from("a  // hi\n b")).unwrap(), "b");
+        assert!(symbol("a").parse_to_end(&mut SliceReader::from("a// end")).is_ok());
+
+        let hash_comments = Trivia::whitespace().with_line_comment("#");
+        let parser = digit().lexeme_with(hash_comments).many();
+        assert_eq!(parser.parse_to_end(&mut SliceReader::from("1 # one\n2")).unwrap(), ['1', '2']);
+    }
+}
@@ -1,40 +1,533 @@
 use std::{fmt, io};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use crate::parse::diagnostic::{Diagnostic, Label};
 
 pub mod combinators;
+pub mod diagnostic;
+pub mod lexeme;
+pub mod numeric;
 pub mod pos_reader;
+pub mod precedence;
+pub mod slice_reader;
+pub mod state;
 pub mod std_parsers;
+pub mod stream_reader;
+
+// A range of byte offsets into the input, from `start` (inclusive) to `end` (exclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Span {
+    pub fn new(start: u64, end: u64) -> Self {
+        Span { start, end }
+    }
+
+    // An empty span at `pos`, used for errors which don't cover any input (like an unexpected eof).
+    pub fn at(pos: u64) -> Self {
+        Span { start: pos, end: pos }
+    }
+}
+
+// A line and column number in the input, both starting from 1. Columns count characters (Unicode scalar values), not
+// bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: u64,
+    pub col: u64,
+}
+
+// A parse result along with the part of the input it was parsed from. The line and column numbers of the start and end
+// of the span are included if the reader keeps track of them (like `PositionReader`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+    pub start: Option<LineCol>,
+    pub end: Option<LineCol>,
+}
+
+impl<T> Spanned<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned { value: f(self.value), span: self.span, start: self.start, end: self.end }
+    }
+}
+
+// Describes a failed parse. `expected` holds descriptions of what would have been accepted at `span` (like `'+'` or
+// `eof`), and `found` describes what was actually there. `message` is for failures which don't fit that mold, and
+// `cause` holds the underlying error if the failure was caused by something other than bad input (like an io error).
+//
+// `labels` point out other parts of the input relevant to the error, and `notes` and `help` add extra information;
+// these are shown when the error is rendered as a `Diagnostic`. `rendered` holds that rendering if it has already been
+// done, as by `PositionTrackingParser`, which knows where in the input parsing started.
+//
+// A `committed` error happened past a commit point (see `CutParser`), so alternatives should not be tried after it.
+//
+// The fields are boxed (and reached through `Deref`), since errors are passed around by value in every `ParseResult`,
+// and most of them are discarded by alternatives and repetitions anyway.
+#[derive(Debug, Clone)]
+pub struct ParseError(Box<ParseErrorInfo>);
 
 #[derive(Debug, Clone)]
-pub struct ParseError {
-    pub reason: String,
+pub struct ParseErrorInfo {
+    pub span: Span,
+    pub expected: Vec<String>,
+    pub found: Option<String>,
+    pub message: Option<String>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub cause: Option<Arc<dyn Error + Send + Sync>>,
+    pub rendered: Option<String>,
+    pub committed: bool,
+}
+
+impl Deref for ParseError {
+    type Target = ParseErrorInfo;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ParseError {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<ParseErrorInfo> for ParseError {
+    fn from(info: ParseErrorInfo) -> Self {
+        ParseError(Box::new(info))
+    }
 }
 
 impl ParseError {
-    pub fn new(reason: &str) -> Self {
-        ParseError { reason: reason.to_string() }
+    pub fn new(pos: u64, message: &str) -> Self {
+        ParseError::from(ParseErrorInfo {
+            span: Span::at(pos),
+            expected: vec![],
+            found: None,
+            message: Some(message.to_string()),
+            labels: vec![],
+            notes: vec![],
+            help: vec![],
+            cause: None,
+            rendered: None,
+            committed: false,
+        })
+    }
+
+    pub fn expected(pos: u64, expected: &str) -> Self {
+        ParseError::new(pos, "").with_message(None).with_expected(vec![expected.to_string()])
+    }
+
+    pub fn io(pos: u64, error: io::Error) -> Self {
+        let message = format!("i/o error: {}", error);
+        ParseError::new(pos, &message).with_cause(Arc::new(error))
+    }
+
+    pub fn with_expected(mut self, expected: Vec<String>) -> Self {
+        self.expected = expected;
+        self
+    }
+
+    pub fn with_found(mut self, found: &str) -> Self {
+        self.found = Some(found.to_string());
+        self
+    }
+
+    pub fn with_message(mut self, message: Option<String>) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_cause(mut self, cause: Arc<dyn Error + Send + Sync>) -> Self {
+        self.cause = Some(cause);
+        self
+    }
+
+    pub fn with_rendered(mut self, rendered: String) -> Self {
+        self.rendered = Some(rendered);
+        self
+    }
+
+    pub fn with_committed(mut self, committed: bool) -> Self {
+        self.committed = committed;
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label::secondary(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    // Renders this error along with the part of `source` it happened in (see `Diagnostic`).
+    pub fn render(&self, source: &[u8], color: bool) -> String {
+        Diagnostic::from_error(self).render(source, color)
+    }
+
+    // Combines two errors, keeping the one which got further into the input. If both failed at the same position, the
+    // result expects anything either of them expected, so `'+'` and `'-'` merge into "expected '+' or '-'".
+    pub fn merge(self, other: ParseError) -> Self {
+        if self.span.start != other.span.start {
+            return if self.span.start > other.span.start { self } else { other };
+        }
+
+        let (first, second) = (*self.0, *other.0);
+        ParseError::from(ParseErrorInfo {
+            span: Span::new(first.span.start, first.span.end.max(second.span.end)),
+            expected: union(first.expected, second.expected),
+            found: first.found.or(second.found),
+            message: first.message.or(second.message),
+            labels: union(first.labels, second.labels),
+            notes: union(first.notes, second.notes),
+            help: union(first.help, second.help),
+            cause: first.cause.or(second.cause),
+            rendered: first.rendered.or(second.rendered),
+            committed: first.committed || second.committed,
+        })
+    }
+
+    // Describes the error without any position information, like "expected '+' or '-', found 'a'".
+    pub fn reason(&self) -> String {
+        let mut parts = vec![];
+        if let Some(message) = &self.message {
+            parts.push(message.clone());
+        }
+        if !self.expected.is_empty() {
+            parts.push(format!("expected {}", join_alternatives(&self.expected)));
+        }
+        if let Some(found) = &self.found {
+            parts.push(format!("found {}", found));
+        }
+        parts.join(", ")
     }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "parse failed: {}", self.reason)
+        write!(f, "parse failed at {}: {}", self.span.start, self.reason())
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.cause.as_ref().map(|cause| cause.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+// Appends the items of `other` which aren't already in `items`.
+fn union<T: PartialEq>(mut items: Vec<T>, other: Vec<T>) -> Vec<T> {
+    for item in other {
+        if !items.contains(&item) {
+            items.push(item);
+        }
     }
+    items
 }
 
-impl<E: Error> From<E> for ParseError {
-    fn from(_: E) -> Self {
-        ParseError::new("exceptional error")
+// Formats a list of alternatives like "a", "a or b", or "a, b or c".
+pub fn join_alternatives(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [init @ .., last] => format!("{} or {}", init.join(", "), last),
     }
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
 
-pub trait ReadSeek: Read + Seek {}
+// Source of input for parsers. The provided methods are written in terms of `Read` and `Seek`, so any such reader can
+// be used with an empty `impl`, or by wrapping it in an `IoReader` if it comes from another crate. In-memory readers
+// like `SliceReader` and `Cursor` override them so that parsers can read and peek at single bytes without going through
+// io calls.
+pub trait ReadSeek: Read + Seek + AsDynReadSeek {
+    // Gets the current position in the stream. If that fails, there's no position to report, so the error is at 0.
+    fn offset(&mut self) -> ParseResult<u64> {
+        self.stream_position().map_err(|e| ParseError::io(0, e))
+    }
+
+    // Moves to `pos`, which should be a position previously returned by `offset`.
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        self.seek(SeekFrom::Start(pos)).map(|_| ()).map_err(|e| ParseError::io(pos, e))
+    }
+
+    // Reads a single byte, returning `None` at eof.
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let mut buf = [0];
+        match self.read(&mut buf) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(buf[0])),
+            Err(e) => Err(ParseError::io(self.offset()?, e)),
+        }
+    }
+
+    // Gets the next byte without consuming it, returning `None` at eof.
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.read_byte()?;
+        if byte.is_some() {
+            if let Err(e) = self.seek(SeekFrom::Current(-1)) {
+                return Err(ParseError::io(self.offset()? - 1, e));
+            }
+        }
+        Ok(byte)
+    }
+
+    // Reads a single UTF-8 encoded character, returning `None` at eof.
+    fn read_char(&mut self) -> ParseResult<Option<char>> {
+        backtrack_on_fail(self, |r| {
+            let pos = r.offset()?;
+            let first = match r.read_byte()? {
+                Some(byte) => byte,
+                _ => return Ok(None),
+            };
+
+            let len = utf8_len(first);
+            let mut buf = [first, 0, 0, 0];
+            for byte in &mut buf[1..len] {
+                match r.read_byte()? {
+                    Some(next) => *byte = next,
+                    _ => break,
+                }
+            }
+            match std::str::from_utf8(&buf[..len]) {
+                Ok(string) => Ok(string.chars().next()),
+                _ => Err(ParseError::new(pos, "invalid UTF-8").with_span(Span::new(pos, r.offset()?))),
+            }
+        })
+    }
+
+    // Gets the next character without consuming it, returning `None` at eof.
+    fn peek_char(&mut self) -> ParseResult<Option<char>> {
+        let pos = self.offset()?;
+        self.mark(pos);
+        let char = self.read_char();
+        let restored = self.seek_to(pos);
+        self.release(pos);
+        restored.and(char)
+    }
+
+    // Tells the reader that it may be asked to seek back to `pos`, until a matching call to `release`. Readers which
+    // can't seek freely (like `StreamReader`) use this to decide how much input to keep buffered.
+    fn mark(&mut self, _pos: u64) {}
+
+    fn release(&mut self, _pos: u64) {}
+
+    // Gets the line and column number of the current position, if this reader keeps track of them.
+    fn line_col(&mut self) -> Option<LineCol> {
+        None
+    }
+
+    // Gets this reader as a trait object. References and boxes hand out the reader they point to instead of wrapping
+    // themselves, so a reader passed through any number of `BoxedParser`s is only ever one call away.
+    fn as_dyn(&mut self) -> &mut dyn ReadSeek where Self: Sized {
+        self
+    }
+}
+
+// Converts any reader to a trait object. This is a supertrait of `ReadSeek` rather than part of it so that it can be
+// implemented for every sized reader at once, while `dyn ReadSeek` gets it through its vtable.
+pub trait AsDynReadSeek {
+    fn as_dyn_read_seek(&mut self) -> &mut dyn ReadSeek;
+}
+
+impl<R: ReadSeek> AsDynReadSeek for R {
+    fn as_dyn_read_seek(&mut self) -> &mut dyn ReadSeek {
+        self
+    }
+}
+
+// Gets the length of a UTF-8 encoded character from its first byte. Invalid first bytes are treated as having a length
+// of 1, so they can be reported on their own.
+pub fn utf8_len(first: u8) -> usize {
+    match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+// Checks whether `byte` continues a UTF-8 encoded character rather than starting one.
+pub fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+impl ReadSeek for File {}
+
+// Wrapper which lets any `Read + Seek` reader be parsed from, using the provided methods of `ReadSeek`.
+pub struct IoReader<R: Read + Seek> {
+    reader: R,
+}
+
+impl<R: Read + Seek> IoReader<R> {
+    pub fn new(reader: R) -> Self {
+        IoReader { reader }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> Read for IoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl<R: Read + Seek> Seek for IoReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.reader.seek(pos)
+    }
+}
+
+impl<R: Read + Seek> ReadSeek for IoReader<R> {}
+
+impl<R: Read + Seek> ReadSeek for BufReader<R> {
+    // `BufReader` throws its buffer away on every call to `seek` (even `SeekFrom::Current(0)`), so these work with the
+    // buffer directly and only go to the inner reader when it's exhausted.
+    fn offset(&mut self) -> ParseResult<u64> {
+        let buffered = self.buffer().len() as u64;
+        let inner = self.get_mut().stream_position().map_err(|e| ParseError::io(0, e))?;
+        Ok(inner - buffered)
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        let current = self.offset()?;
+        self.seek_relative(pos as i64 - current as i64).map_err(|e| ParseError::io(pos, e))
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if byte.is_some() {
+            self.consume(1);
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        match self.fill_buf() {
+            Ok(buf) => Ok(buf.first().copied()),
+            Err(e) => Err(ParseError::io(self.offset()?, e)),
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> ReadSeek for Cursor<T> {
+    fn offset(&mut self) -> ParseResult<u64> {
+        Ok(self.position())
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        self.set_position(pos);
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if byte.is_some() {
+            self.set_position(self.position() + 1);
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        Ok(self.get_ref().as_ref().get(self.position() as usize).copied())
+    }
+}
+
+impl<R: ReadSeek + ?Sized> ReadSeek for &mut R {
+    fn offset(&mut self) -> ParseResult<u64> {
+        (**self).offset()
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        (**self).seek_to(pos)
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        (**self).read_byte()
+    }
 
-impl<T: Read + Seek> ReadSeek for T {}
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        (**self).peek_byte()
+    }
+
+    fn mark(&mut self, pos: u64) {
+        (**self).mark(pos)
+    }
+
+    fn release(&mut self, pos: u64) {
+        (**self).release(pos)
+    }
+
+    fn line_col(&mut self) -> Option<LineCol> {
+        (**self).line_col()
+    }
+
+    fn as_dyn(&mut self) -> &mut dyn ReadSeek {
+        (**self).as_dyn_read_seek()
+    }
+}
+
+impl<R: ReadSeek + ?Sized> ReadSeek for Box<R> {
+    fn offset(&mut self) -> ParseResult<u64> {
+        (**self).offset()
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        (**self).seek_to(pos)
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        (**self).read_byte()
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        (**self).peek_byte()
+    }
+
+    fn mark(&mut self, pos: u64) {
+        (**self).mark(pos)
+    }
+
+    fn release(&mut self, pos: u64) {
+        (**self).release(pos)
+    }
+
+    fn line_col(&mut self) -> Option<LineCol> {
+        (**self).line_col()
+    }
+
+    fn as_dyn(&mut self) -> &mut dyn ReadSeek {
+        (**self).as_dyn_read_seek()
+    }
+}
 
 pub trait Parser {
     type Output;
@@ -42,13 +535,63 @@ pub trait Parser {
     // Parses data from `reader` until the parser is finished or an error occurs.
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized;
 
-    // Like `parse`, but ensures `reader` contains no more data to parse if successful.
+    // Like `parse`, but ensures `reader` contains no more data to parse if successful. This runs as its own session
+    // (see `state::session`), so on failure, it reports the error which got furthest into the input, even if it was
+    // swallowed by something like `many` along the way.
     fn parse_to_end(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
-        self.parse(reader).and_then(|v| {
-            std_parsers::eof.parse(reader)?;
-            Ok(v)
+        state::session(|| {
+            self.parse(reader).and_then(|v| {
+                std_parsers::eof.parse(reader)?;
+                Ok(v)
+            })
         })
     }
+
+    // Like `parse_to_end`, but returns a partial result along with every error recovered from (see `RecoverParser`)
+    // instead of failing on the first one. If parsing failed entirely, there is no result and the error is last.
+    fn parse_to_end_recovering(&self, reader: &mut impl ReadSeek) -> (Option<Self::Output>, Vec<ParseError>)
+        where Self: Sized
+    {
+        let (result, mut errors) = state::recovering_session(|| {
+            self.parse(reader).and_then(|v| {
+                std_parsers::eof.parse(reader)?;
+                Ok(v)
+            })
+        });
+        match result {
+            Ok(v) => (Some(v), errors),
+            Err(error) => {
+                errors.push(error);
+                (None, errors)
+            }
+        }
+    }
+}
+
+// Object safe counterpart to `Parser`, which takes its reader as a trait object. Every `Parser` is a `DynParser`, and
+// `BoxedParser` goes the other way, so parsers can be stored in collections or chosen at runtime.
+pub trait DynParser {
+    type Output;
+
+    fn parse_dyn(&self, reader: &mut dyn ReadSeek) -> ParseResult<Self::Output>;
+}
+
+impl<P: Parser> DynParser for P {
+    type Output = P::Output;
+
+    fn parse_dyn(&self, mut reader: &mut dyn ReadSeek) -> ParseResult<Self::Output> {
+        self.parse(&mut reader)
+    }
+}
+
+pub type BoxedParser<'a, T> = Box<dyn DynParser<Output=T> + 'a>;
+
+impl<'a, T> Parser for BoxedParser<'a, T> {
+    type Output = T;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        self.as_ref().parse_dyn(reader.as_dyn())
+    }
 }
 
 impl<P: Parser, F: Fn() -> P> Parser for F {
@@ -59,8 +602,8 @@ impl<P: Parser, F: Fn() -> P> Parser for F {
     }
 }
 
-impl Parser for &str {
-    type Output = String;
+impl<'a> Parser for &'a str {
+    type Output = &'a str;
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> where Self: Sized {
         std_parsers::string(self).parse(reader)
@@ -68,19 +611,198 @@ impl Parser for &str {
 }
 
 // Saves the position of `reader` and calls `f`, seeking `reader` back to its original position if `f` failed. This is
-// used to implement backtracking.
+// used to implement backtracking. Errors recovered from inside `f` are forgotten too, since the input will be parsed
+// again. The position is marked (see `ReadSeek::mark`) while `f` runs, so the reader keeps it reachable.
 pub fn backtrack_on_fail<T, R, F>(reader: &mut R, mut f: F) -> ParseResult<T>
-    where R: ReadSeek,
+    where R: ReadSeek + ?Sized,
           F: FnMut(&mut R) -> ParseResult<T>
 {
-    let initial_pos = reader.stream_position().unwrap();
+    let initial_pos = reader.offset()?;
+    let recovered_count = state::recovered_count();
+    reader.mark(initial_pos);
     let result = f(reader);
-    if result.is_err() {
-        reader.seek(SeekFrom::Start(initial_pos))?;
+
+    let restored = match result {
+        Err(_) => {
+            state::truncate_recovered(recovered_count);
+            reader.seek_to(initial_pos)
+        }
+        _ => Ok(()),
+    };
+    reader.release(initial_pos);
+    restored.and(result)
+}
+
+// Consumes `text` if the input continues with it, returning whether it did.
+pub fn eat(reader: &mut impl ReadSeek, text: &str) -> ParseResult<bool> {
+    let result = backtrack_on_fail(reader, |r| {
+        for expected in text.bytes() {
+            if r.read_byte()? != Some(expected) {
+                return Err(ParseError::expected(0, text));
+            }
+        }
+        Ok(())
+    });
+    match result {
+        Ok(_) => Ok(true),
+        Err(error) if error.cause.is_some() => Err(error),
+        _ => Ok(false),
     }
-    result
 }
 
-pub fn seek_back_one(reader: &mut impl ReadSeek) -> io::Result<u64> {
-    reader.seek(SeekFrom::Current(-1))
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+
+    use crate::parse::{IoReader, ParseError, Parser, ReadSeek, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::lexeme::*;
+    use crate::parse::numeric::integer;
+    use crate::parse::std_parsers::*;
+
+    #[test]
+    fn reason_lists_expected_and_found() {
+        let error = ParseError::expected(3, "'+'").with_found("'a'").with_span(Span::new(3, 4));
+        assert_eq!(error.reason(), "expected '+', found 'a'");
+        assert_eq!(error.to_string(), "parse failed at 3: expected '+', found 'a'");
+    }
+
+    #[test]
+    fn reason_joins_alternatives() {
+        let mut error = ParseError::expected(0, "'+'");
+        error.expected.extend(["'-'".to_string(), "eof".to_string()]);
+        assert_eq!(error.reason(), "expected '+', '-' or eof");
+    }
+
+    #[test]
+    fn io_error_is_the_source() {
+        let error = ParseError::io(5, io::Error::other("disk on fire"));
+        assert_eq!(error.span, Span::at(5));
+        assert_eq!(error.reason(), "i/o error: disk on fire");
+        assert_eq!(error.source().unwrap().to_string(), "disk on fire");
+        assert!(ParseError::new(0, "bad").source().is_none());
+    }
+
+    #[test]
+    fn failed_string_reports_what_was_found() {
+        let error = string("abc").parse(&mut Cursor::new("abd".as_bytes())).unwrap_err();
+        assert_eq!(error.span.start, 0);
+        assert_eq!(error.expected, ["'abc'"]);
+    }
+
+    #[test]
+    fn eof_reports_trailing_input() {
+        let error = eof.parse(&mut Cursor::new("x".as_bytes())).unwrap_err();
+        assert_eq!(error.expected, ["eof"]);
+        assert_eq!(error.found.as_deref(), Some("'x'"));
+    }
+
+    #[test]
+    fn merge_at_same_position_unions_expected() {
+        let first = ParseError::expected(2, "'+'").with_found("'a'");
+        let merged = first.merge(ParseError::expected(2, "'-'")).merge(ParseError::expected(2, "'+'"));
+        assert_eq!(merged.reason(), "expected '+' or '-', found 'a'");
+    }
+
+    #[test]
+    fn merge_keeps_error_which_got_further() {
+        let near = ParseError::expected(1, "'+'");
+        let far = ParseError::expected(4, "digit");
+        assert_eq!(near.clone().merge(far.clone()).span.start, 4);
+        assert_eq!(far.merge(near).expected, ["digit"]);
+    }
+
+    #[test]
+    fn peeking_does_not_consume() {
+        let mut reader = BufReader::new(Cursor::new(b"ab".to_vec()));
+        assert_eq!(reader.peek_byte().unwrap(), Some(b'a'));
+        assert_eq!(reader.read_byte().unwrap(), Some(b'a'));
+        assert_eq!(reader.offset().unwrap(), 1);
+        reader.seek_to(0).unwrap();
+        assert_eq!(string("ab").parse(&mut reader).unwrap(), "ab");
+        assert_eq!(reader.peek_byte().unwrap(), None);
+    }
+
+    #[test]
+    fn reads_utf8_characters() {
+        let mut reader = Cursor::new("é€𝄞".as_bytes());
+        assert_eq!(reader.peek_char().unwrap(), Some('é'));
+        let chars = (0..4).map(|_| reader.read_char().unwrap()).collect::<Vec<_>>();
+        assert_eq!(chars, [Some('é'), Some('€'), Some('𝄞'), None]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error_without_consuming() {
+        for input in [&b"\xff"[..], b"\xe2\x82", b"\xc3("] {
+            let mut reader = Cursor::new(input);
+            let error = reader.read_char().unwrap_err();
+            assert_eq!(error.reason(), "invalid UTF-8");
+            assert_eq!(error.span.start, 0);
+            assert_eq!(reader.offset().unwrap(), 0);
+        }
+    }
+
+    // Counts the reads which reach the underlying reader.
+    struct CountingReader {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn buf_reader_reads_are_served_from_its_buffer() {
+        let input = (0..1000).map(|i| format!("{} ", i)).collect::<String>();
+        let mut reader = BufReader::new(CountingReader { inner: Cursor::new(input.into_bytes()), reads: 0 });
+        let numbers = integer::<i32>().lexeme().many().parse_to_end(&mut reader).unwrap();
+        assert_eq!(numbers.len(), 1000);
+        assert!(reader.get_ref().reads < 20);
+    }
+
+    #[test]
+    fn io_readers_wrap_any_read_seek() {
+        let mut reader = IoReader::new(CountingReader { inner: Cursor::new(b"12 34".to_vec()), reads: 0 });
+        let numbers = integer::<i32>().lexeme().many().parse_to_end(&mut reader).unwrap();
+        assert_eq!(numbers, [12, 34]);
+        assert!(reader.into_inner().reads > 0);
+    }
+
+    // Reads from a cursor, but can't seek backwards.
+    struct ForwardOnly(Cursor<Vec<u8>>);
+
+    impl Read for ForwardOnly {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for ForwardOnly {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match pos {
+                SeekFrom::Current(offset) if offset < 0 => Err(io::Error::other("can't seek backwards")),
+                _ => self.0.seek(pos),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_peeks_report_the_peeked_position() {
+        let mut reader = IoReader::new(ForwardOnly(Cursor::new(b"abc".to_vec())));
+        reader.read_byte().unwrap();
+        let error = reader.peek_byte().unwrap_err();
+        assert_eq!(error.span.start, 1);
+        assert!(error.cause.is_some());
+    }
 }
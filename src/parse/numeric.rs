@@ -0,0 +1,290 @@
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use num::{Float, Num};
+
+use crate::parse;
+use crate::parse::{ParseError, Parser, ParseResult, ReadSeek, Span};
+
+// Parses an integer literal into any `Num` type (including big integers). By default, this accepts an optional sign,
+// a `0x`, `0o`, or `0b` prefix for hexadecimal, octal, or binary, and `_` separators between digits, like `-0xff_ff`;
+// each of these can be turned off.
+pub struct IntegerParser<I: Num> {
+    sign: bool,
+    prefixes: bool,
+    separators: bool,
+    phantom: PhantomData<I>,
+}
+
+impl<I: Num> IntegerParser<I> {
+    pub fn with_sign(self, sign: bool) -> Self {
+        IntegerParser { sign, ..self }
+    }
+
+    pub fn with_prefixes(self, prefixes: bool) -> Self {
+        IntegerParser { prefixes, ..self }
+    }
+
+    pub fn with_separators(self, separators: bool) -> Self {
+        IntegerParser { separators, ..self }
+    }
+
+    // Accepts a suffix from `suffixes` after the literal (see `SuffixedParser`).
+    pub fn with_suffixes<'a>(self, suffixes: &[&'a str]) -> SuffixedParser<'a, Self> {
+        SuffixedParser::new(self, suffixes)
+    }
+}
+
+impl<I: Num> Parser for IntegerParser<I> {
+    type Output = I;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let mut digits = if self.sign { read_sign(r)? } else { String::new() };
+
+            let (radix, expected) = match self.prefixes {
+                true if parse::eat(r, "0x")? => (16, "hexadecimal digit"),
+                true if parse::eat(r, "0o")? => (8, "octal digit"),
+                true if parse::eat(r, "0b")? => (2, "binary digit"),
+                _ => (10, "integer"),
+            };
+            if read_digits(r, radix, self.separators, &mut digits)? == 0 {
+                let pos = r.offset()?;
+                return Err(ParseError::expected(pos, expected));
+            }
+
+            I::from_str_radix(&digits, radix).map_err(|_| {
+                let message = format!("integer literal out of range: {}", digits);
+                ParseError::new(start, &message).with_span(Span::new(start, r.offset().unwrap_or(start)))
+            })
+        })
+    }
+}
+
+pub fn integer<I: Num>() -> IntegerParser<I> {
+    IntegerParser { sign: true, prefixes: true, separators: true, phantom: PhantomData }
+}
+
+// Parses a floating point literal into any `Float` type, like `1`, `-2.5`, or `6.02e23`. By default, this accepts an
+// optional sign, an exponent, `_` separators between digits, and `inf` (or `infinity`) and `nan`; each of these can be
+// turned off. A `.` must be followed by a digit to be part of the literal, so `1.` parses as `1`, leaving the `.`; the
+// same goes for an exponent, so `1e` and `1e+` parse as `1` too.
+pub struct FloatParser<F: Float + FromStr> {
+    sign: bool,
+    exponent: bool,
+    separators: bool,
+    special: bool,
+    phantom: PhantomData<F>,
+}
+
+impl<F: Float + FromStr> FloatParser<F> {
+    pub fn with_sign(self, sign: bool) -> Self {
+        FloatParser { sign, ..self }
+    }
+
+    pub fn with_exponent(self, exponent: bool) -> Self {
+        FloatParser { exponent, ..self }
+    }
+
+    pub fn with_separators(self, separators: bool) -> Self {
+        FloatParser { separators, ..self }
+    }
+
+    // Sets whether `inf` and `nan` are accepted.
+    pub fn with_special(self, special: bool) -> Self {
+        FloatParser { special, ..self }
+    }
+
+    // Accepts a suffix from `suffixes` after the literal (see `SuffixedParser`).
+    pub fn with_suffixes<'a>(self, suffixes: &[&'a str]) -> SuffixedParser<'a, Self> {
+        SuffixedParser::new(self, suffixes)
+    }
+}
+
+impl<F: Float + FromStr> Parser for FloatParser<F> {
+    type Output = F;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let mut text = if self.sign { read_sign(r)? } else { String::new() };
+            let negative = text == "-";
+
+            if self.special && (parse::eat(r, "infinity")? || parse::eat(r, "inf")?) {
+                return Ok(if negative { F::neg_infinity() } else { F::infinity() });
+            }
+            if self.special && parse::eat(r, "nan")? {
+                return Ok(F::nan());
+            }
+
+            if read_digits(r, 10, self.separators, &mut text)? == 0 {
+                let pos = r.offset()?;
+                return Err(ParseError::expected(pos, "number"));
+            }
+            if r.peek_byte()? == Some(b'.') {
+                let dot = r.offset()?;
+                r.read_byte()?;
+                text.push('.');
+                if read_digits(r, 10, self.separators, &mut text)? == 0 {
+                    r.seek_to(dot)?;
+                    text.pop();
+                }
+            }
+            if self.exponent && matches!(r.peek_byte()?, Some(b'e') | Some(b'E')) {
+                let (e, mantissa_len) = (r.offset()?, text.len());
+                r.read_byte()?;
+                text.push('e');
+                text += &read_sign(r)?;
+                if read_digits(r, 10, self.separators, &mut text)? == 0 {
+                    r.seek_to(e)?;
+                    text.truncate(mantissa_len);
+                }
+            }
+
+            text.parse::<F>().map_err(|_| {
+                let message = format!("invalid float literal: {}", text);
+                ParseError::new(start, &message).with_span(Span::new(start, r.offset().unwrap_or(start)))
+            })
+        })
+    }
+}
+
+pub fn float<F: Float + FromStr>() -> FloatParser<F> {
+    FloatParser { sign: true, exponent: true, separators: true, special: true, phantom: PhantomData }
+}
+
+// Parses `parser`, then an optional suffix from `suffixes`, returning both. The longest matching suffix is used, so
+// with `i8` and `i16` as suffixes, `5i16` has the suffix `i16`. This is for typed literals like `255u8` or `1.5f32`;
+// the value is parsed as a single type, which can be converted to the one the suffix names with `num::cast`.
+pub struct SuffixedParser<'a, P: Parser> {
+    parser: P,
+    suffixes: Vec<&'a str>,
+}
+
+impl<'a, P: Parser> SuffixedParser<'a, P> {
+    pub fn new(parser: P, suffixes: &[&'a str]) -> Self {
+        let mut suffixes = suffixes.to_vec();
+        suffixes.sort_by_key(|suffix| std::cmp::Reverse(suffix.len()));
+        SuffixedParser { parser, suffixes }
+    }
+}
+
+impl<'a, P: Parser> Parser for SuffixedParser<'a, P> {
+    type Output = (P::Output, Option<&'a str>);
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let value = self.parser.parse(r)?;
+            for suffix in &self.suffixes {
+                if parse::eat(r, suffix)? {
+                    return Ok((value, Some(*suffix)));
+                }
+            }
+            Ok((value, None))
+        })
+    }
+}
+
+// Reads an optional `+` or `-`, returning `"-"` if it was a minus sign and an empty string otherwise.
+fn read_sign(reader: &mut impl ReadSeek) -> ParseResult<String> {
+    match reader.peek_byte()? {
+        Some(b'-') => {
+            reader.read_byte()?;
+            Ok("-".to_string())
+        }
+        Some(b'+') => {
+            reader.read_byte()?;
+            Ok(String::new())
+        }
+        _ => Ok(String::new()),
+    }
+}
+
+// Reads digits in `radix`, appending them to `digits` and returning how many were read. If `separators` is set, `_` is
+// skipped anywhere after the first digit.
+fn read_digits(reader: &mut impl ReadSeek, radix: u32, separators: bool, digits: &mut String) -> ParseResult<usize> {
+    let mut count = 0;
+    while let Some(byte) = reader.peek_byte()? {
+        if (byte as char).is_digit(radix) {
+            digits.push(byte as char);
+            count += 1;
+        } else if byte != b'_' || !separators || count == 0 {
+            break;
+        }
+        reader.read_byte()?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{Parser, ReadSeek};
+    use crate::parse::numeric::*;
+    use crate::parse::slice_reader::SliceReader;
+    use num::BigInt;
+
+    #[test]
+    fn integers_with_prefixes_and_separators() {
+        let parse = |input| integer::<i64>().parse(&mut SliceReader::from(input)).unwrap();
+        assert_eq!(parse("-0xff_ff"), -0xffff);
+        assert_eq!(parse("+0o17"), 0o17);
+        assert_eq!(parse("0b1010_1010"), 0b1010_1010);
+        assert_eq!(parse("1_000_000"), 1_000_000);
+    }
+
+    #[test]
+    fn integer_options_can_be_turned_off() {
+        let mut reader = SliceReader::from("0x10");
+        assert_eq!(integer::<u32>().with_prefixes(false).parse(&mut reader).unwrap(), 0);
+        assert_eq!(reader.rest(), b"x10");
+        assert!(integer::<i32>().with_sign(false).parse(&mut SliceReader::from("-1")).is_err());
+        let mut reader = SliceReader::from("1_0");
+        assert_eq!(integer::<i32>().with_separators(false).parse(&mut reader).unwrap(), 1);
+    }
+
+    #[test]
+    fn integers_out_of_range_are_errors() {
+        let error = integer::<u8>().parse(&mut SliceReader::from("256")).unwrap_err();
+        assert_eq!(error.reason(), "integer literal out of range: 256");
+        let big = integer::<BigInt>().parse(&mut SliceReader::from("123456789012345678901234567890")).unwrap();
+        assert_eq!(big.to_string(), "123456789012345678901234567890");
+    }
+
+    #[test]
+    fn floats() {
+        let parse = |input| float::<f64>().parse(&mut SliceReader::from(input)).unwrap();
+        assert_eq!(parse("-2.5"), -2.5);
+        assert_eq!(parse("6.02e23"), 6.02e23);
+        assert_eq!(parse("1_0.2_5E-1"), 1.025);
+        assert_eq!(parse("-inf"), f64::NEG_INFINITY);
+        assert!(parse("nan").is_nan());
+    }
+
+    #[test]
+    fn float_dot_needs_a_digit_after_it() {
+        let mut reader = SliceReader::from("1.x");
+        assert_eq!(float::<f32>().parse(&mut reader).unwrap(), 1.0);
+        assert_eq!(reader.offset().unwrap(), 1);
+    }
+
+    #[test]
+    fn float_exponent_needs_a_digit() {
+        for (input, rest) in [("1ex", "ex"), ("2.5e+", "e+"), ("3E-_", "E-_")] {
+            let mut reader = SliceReader::from(input);
+            assert!(float::<f64>().parse(&mut reader).is_ok());
+            assert_eq!(reader.rest(), rest.as_bytes());
+        }
+        let parser = float::<f64>().with_suffixes(&["em"]);
+        assert_eq!(parser.parse(&mut SliceReader::from("2em")).unwrap(), (2.0, Some("em")));
+    }
+
+    #[test]
+    fn longest_suffix_is_used() {
+        let parser = integer::<i64>().with_suffixes(&["i8", "i16", "u8"]);
+        assert_eq!(parser.parse(&mut SliceReader::from("5i16")).unwrap(), (5, Some("i16")));
+        assert_eq!(parser.parse(&mut SliceReader::from("7")).unwrap(), (7, None));
+        let parser = float::<f64>().with_suffixes(&["f32"]);
+        assert_eq!(parser.parse(&mut SliceReader::from("1.5f32")).unwrap(), (1.5, Some("f32")));
+    }
+}
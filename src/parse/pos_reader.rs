@@ -1,63 +1,79 @@
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 use std::io;
 
-use crate::parse::ReadSeek;
+use crate::parse;
+use crate::parse::{LineCol, ParseResult, ReadSeek};
 
-// Wrapper around a `ReadSeek` which stores position information, useful for reporting errors.
+// Wrapper around a `ReadSeek` which stores position information, useful for reporting errors. Lines and columns are
+// counted from wherever `reader` was when this was created, which is line 1, column 1 unless a different `base` is set
+// (for input which doesn't start at the beginning of a file, like a code block embedded in a larger document).
 pub struct PositionReader<'a, R: ReadSeek> {
     reader: &'a mut R,
-    stream_len: u64,
+    start: u64,
+    base: LineCol,
 
     pos: u64,
     line: u64,
     col: u64,
 
-    // Stores the lengths of lines previously read so that the column can be determined when seeking backwards. This
-    // also requires `reader` to initially be at the beginning of its stream; otherwise, seeking before the initial
-    // position would make determining the column prohibitively expensive.
-    line_lens: Vec<u64>,
+    // Stores the lengths of lines previously read (in bytes and in characters) so that the column can be determined
+    // when seeking backwards. Seeking before `start` isn't allowed, since determining the column there would be
+    // prohibitively expensive.
+    line_lens: Vec<(u64, u64)>,
 }
 
 impl<'a, R: ReadSeek> PositionReader<'a, R> {
-    pub fn new(reader: &'a mut R) -> Option<Self> {
-        if reader.stream_position().unwrap_or(1) > 0 {
-            None
-        } else {
-            let stream_len = reader.stream_len().unwrap();
-            Some(PositionReader {
-                reader,
-                stream_len,
-                pos: 0,
-                line: 0,
-                col: 0,
-                line_lens: vec![0],
-            })
-        }
+    pub fn new(reader: &'a mut R) -> ParseResult<Self> {
+        let start = reader.offset()?;
+        Ok(PositionReader {
+            reader,
+            start,
+            base: LineCol { line: 1, col: 1 },
+            pos: start,
+            line: 0,
+            col: 0,
+            line_lens: vec![(0, 0)],
+        })
     }
 
-    // Gets the current line with the internal state of the reader appearing unchanged afterward.
-    pub fn current_line(&mut self) -> Option<String> {
-        let old_pos = self.position();
-        self.reader.seek(SeekFrom::Current(-(self.col as i64))).ok()?;
-
-        let mut buf = Vec::with_capacity(self.col as usize);
-        let mut buf_read = BufReader::new(&mut self.reader);
-        buf_read.read_until(b'\n', &mut buf).ok()?;
-
-        self.reader.seek(SeekFrom::Start(old_pos)).ok()?;
-        String::from_utf8(buf).ok()
+    // Sets the line and column number of the position the reader started at.
+    pub fn with_base(self, base: LineCol) -> Self {
+        PositionReader { base, ..self }
     }
 
     pub fn position(&self) -> u64 {
         self.pos
     }
 
+    // Gets the current line number, starting from 0.
     pub fn line(&self) -> u64 {
-        self.line
+        self.base.line - 1 + self.line
     }
 
+    // Gets the current column number (the number of characters before the current position on its line), starting
+    // from 0.
     pub fn col(&self) -> u64 {
-        self.col
+        if self.line == 0 { self.base.col - 1 + self.col } else { self.col }
+    }
+
+}
+
+impl<'a, R: ReadSeek> PositionReader<'a, R> {
+    // Updates the position information after `byte` was read.
+    fn advance(&mut self, byte: u8) {
+        self.pos += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.col = 0;
+            self.line_lens.push((0, 0));
+        } else {
+            // Bytes continuing a UTF-8 encoded character are part of the column its first byte started.
+            let new_char = !parse::is_utf8_continuation(byte) as u64;
+            self.col += new_char;
+            let (bytes, chars) = &mut self.line_lens[self.line as usize];
+            *bytes += 1;
+            *chars += new_char;
+        }
     }
 }
 
@@ -65,14 +81,7 @@ impl<'a, R: ReadSeek> Read for PositionReader<'a, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let read = self.reader.read(buf)?;
         for byte in &buf[..read] {
-            if *byte == b'\n' {
-                self.line += 1;
-                self.col = 0;
-                self.line_lens.push(0);
-            }
-            self.pos += 1;
-            self.col += 1;
-            self.line_lens[self.line as usize] += 1;
+            self.advance(*byte);
         }
         Ok(read)
     }
@@ -82,9 +91,17 @@ impl<'a, R: ReadSeek> Seek for PositionReader<'a, R> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         let offset_from_start = match pos {
             SeekFrom::Start(offset) => offset as i64,
-            SeekFrom::End(offset) => self.stream_len as i64 - offset - 1,
+            SeekFrom::End(offset) => {
+                let end = self.reader.seek(SeekFrom::End(0))?;
+                self.reader.seek(SeekFrom::Start(self.pos))?;
+                end as i64 + offset
+            }
             SeekFrom::Current(offset) => self.pos as i64 + offset,
         };
+        if offset_from_start < self.start as i64 {
+            let message = "cannot seek before the position a PositionReader started at";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
 
         let diff = offset_from_start - self.pos as i64;
         if diff == 0 {
@@ -95,30 +112,84 @@ impl<'a, R: ReadSeek> Seek for PositionReader<'a, R> {
 
         let mut buf = vec![0; seeked as usize];
         if is_forward {
-            self.read_exact(&mut buf).unwrap();
+            self.read_exact(&mut buf)?;
             Ok(self.pos)
         } else {
-            self.reader.seek(pos)?;
-            self.reader.read_exact(&mut buf).unwrap();
+            let target = SeekFrom::Start(offset_from_start as u64);
+            self.reader.seek(target)?;
+            self.reader.read_exact(&mut buf)?;
 
             // Modify positions accordingly.
             let line_diff = buf.iter().filter(|b| **b == b'\n').count() as u64;
             self.line -= line_diff;
             self.pos -= seeked;
 
-            // Calculate the column number.
-            let n_before_line_feed = buf.iter().take_while(|b| **b != b'\n').count() as u64;
-            self.line_lens[self.line as usize] -= n_before_line_feed;
-            self.col = self.line_lens[self.line as usize];
-            self.reader.seek(pos)
+            // Calculate the column number. Lines after the current one will be counted again when they are read.
+            let before_line_feed = buf.iter().take_while(|b| **b != b'\n');
+            let chars_before_line_feed = before_line_feed.clone().filter(|b| !parse::is_utf8_continuation(**b)).count();
+            self.line_lens.truncate(self.line as usize + 1);
+
+            let (bytes, chars) = &mut self.line_lens[self.line as usize];
+            *bytes -= before_line_feed.count() as u64;
+            *chars -= chars_before_line_feed as u64;
+            self.col = *chars;
+            self.reader.seek(target)
         }
     }
 
-    fn stream_len(&mut self) -> io::Result<u64> {
-        Ok(self.stream_len)
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
     }
+}
 
-    fn stream_position(&mut self) -> io::Result<u64> {
+impl<'a, R: ReadSeek> ReadSeek for PositionReader<'a, R> {
+    fn offset(&mut self) -> ParseResult<u64> {
         Ok(self.pos)
     }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.reader.read_byte()?;
+        if let Some(byte) = byte {
+            self.advance(byte);
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        self.reader.peek_byte()
+    }
+
+    fn mark(&mut self, pos: u64) {
+        self.reader.mark(pos)
+    }
+
+    fn release(&mut self, pos: u64) {
+        self.reader.release(pos)
+    }
+
+    fn line_col(&mut self) -> Option<LineCol> {
+        Some(LineCol { line: self.line() + 1, col: self.col() + 1 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::parse::{LineCol, ReadSeek};
+    use crate::parse::pos_reader::PositionReader;
+
+    #[test]
+    fn columns_count_characters() {
+        let mut input = Cursor::new("aé\nü!".as_bytes());
+        let mut reader = PositionReader::new(&mut input).unwrap();
+        while reader.read_char().unwrap() != Some('é') {}
+        assert_eq!(reader.line_col(), Some(LineCol { line: 1, col: 3 }));
+        while reader.read_char().unwrap() != Some('ü') {}
+        assert_eq!(reader.line_col(), Some(LineCol { line: 2, col: 2 }));
+
+        reader.seek_to(1).unwrap();
+        assert_eq!(reader.line_col(), Some(LineCol { line: 1, col: 2 }));
+        assert_eq!(reader.read_char().unwrap(), Some('é'));
+    }
 }
@@ -0,0 +1,182 @@
+use crate::parse;
+use crate::parse::{BoxedParser, ParseError, Parser, ParseResult, ReadSeek, Span};
+use crate::parse::combinators::{BoxedParserExt, MapParserExt};
+use crate::parse::state;
+
+// Associativity of an infix operator. `a - b - c` is `(a - b) - c` if `-` is left associative, `a - (b - c)` if it is
+// right associative, and an error if it is non-associative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+// An operator along with the function building its result. `assoc` only matters for infix operators.
+struct Operator<'a, F: ?Sized> {
+    op: BoxedParser<'a, ()>,
+    precedence: u32,
+    assoc: Assoc,
+    build: Box<F>,
+}
+
+// Parses expressions made of `atom`s and prefix, postfix, and infix operators, using precedence climbing (Pratt
+// parsing). Operators with higher precedence bind more tightly, and the `build` function given with each operator turns
+// its operands into a `T`:
+//
+//   PrecedenceParser::new(number)
+//       .prefix("-", 3, |a| -a)
+//       .infix("+", 1, Assoc::Left, |a, b| a + b)
+//       .infix("*", 2, Assoc::Left, |a, b| a * b)
+//       .infix("^", 4, Assoc::Right, |a, b| a.pow(b))
+//
+// Operators are tried in the order they were added, so an operator which is a prefix of another (like `<` and `<=`)
+// should be added after it.
+pub struct PrecedenceParser<'a, A: Parser<Output=T>, T> {
+    atom: A,
+    prefix: Vec<Operator<'a, dyn Fn(T) -> T + 'a>>,
+    postfix: Vec<Operator<'a, dyn Fn(T) -> T + 'a>>,
+    infix: Vec<Operator<'a, dyn Fn(T, T) -> T + 'a>>,
+}
+
+impl<'a, A: Parser<Output=T>, T> PrecedenceParser<'a, A, T> {
+    pub fn new(atom: A) -> Self {
+        PrecedenceParser { atom, prefix: vec![], postfix: vec![], infix: vec![] }
+    }
+
+    pub fn prefix<P: Parser + 'a>(mut self, op: P, precedence: u32, build: impl Fn(T) -> T + 'a) -> Self {
+        let op = op.map(|_| ()).boxed();
+        self.prefix.push(Operator { op, precedence, assoc: Assoc::None, build: Box::new(build) });
+        self
+    }
+
+    pub fn postfix<P: Parser + 'a>(mut self, op: P, precedence: u32, build: impl Fn(T) -> T + 'a) -> Self {
+        let op = op.map(|_| ()).boxed();
+        self.postfix.push(Operator { op, precedence, assoc: Assoc::None, build: Box::new(build) });
+        self
+    }
+
+    pub fn infix<P>(mut self, op: P, precedence: u32, assoc: Assoc, build: impl Fn(T, T) -> T + 'a) -> Self
+        where P: Parser + 'a
+    {
+        self.infix.push(Operator { op: op.map(|_| ()).boxed(), precedence, assoc, build: Box::new(build) });
+        self
+    }
+
+    // Parses an expression whose operators all bind at least as tightly as `min_bp`. Binding powers are derived from
+    // precedences so that the left and right sides of an infix operator differ, which is what makes it associative.
+    fn parse_expr(&self, reader: &mut impl ReadSeek, min_bp: u32) -> ParseResult<T> {
+        let mut lhs = match find_op(&self.prefix, reader)? {
+            Some(prefix) => (prefix.build)(self.parse_expr(reader, prefix.precedence * 2)?),
+            None => self.atom.parse(reader)?,
+        };
+        let mut non_assoc = None;
+
+        loop {
+            let pos = reader.offset()?;
+            if let Some(postfix) = find_op(&self.postfix, reader)? {
+                if postfix.precedence * 2 < min_bp {
+                    reader.seek_to(pos)?;
+                    break;
+                }
+                lhs = (postfix.build)(lhs);
+                continue;
+            }
+
+            let infix = match find_op(&self.infix, reader)? {
+                Some(infix) => infix,
+                None => break,
+            };
+            let (left_bp, right_bp) = match infix.assoc {
+                Assoc::Left | Assoc::None => (infix.precedence * 2, infix.precedence * 2 + 1),
+                Assoc::Right => (infix.precedence * 2 + 1, infix.precedence * 2),
+            };
+            if left_bp < min_bp {
+                reader.seek_to(pos)?;
+                break;
+            }
+            let op_span = Span::new(pos, reader.offset()?);
+            if let Some((bp, previous_span)) = non_assoc {
+                if bp == left_bp {
+                    return Err(ParseError::new(pos, "non-associative operators cannot be chained")
+                        .with_span(op_span)
+                        .with_label(previous_span, "previous operator")
+                        .with_help("add parentheses to make the grouping explicit")
+                        .with_committed(true));
+                }
+            }
+
+            let rhs = self.parse_expr(reader, right_bp)?;
+            lhs = (infix.build)(lhs, rhs);
+            non_assoc = if infix.assoc == Assoc::None { Some((left_bp, op_span)) } else { None };
+        }
+        Ok(lhs)
+    }
+}
+
+// Finds the first operator in `ops` which parses successfully. Errors from the operators which didn't are recorded
+// rather than returned, unless they are committed.
+fn find_op<'o, 'a, F, R>(ops: &'o [Operator<'a, F>], reader: &mut R) -> ParseResult<Option<&'o Operator<'a, F>>>
+    where F: ?Sized,
+          R: ReadSeek
+{
+    for op in ops {
+        match op.op.parse(reader) {
+            Ok(_) => return Ok(Some(op)),
+            Err(error) if error.committed => return Err(error),
+            Err(error) => state::record_failure(&error),
+        }
+    }
+    Ok(None)
+}
+
+impl<'a, A: Parser<Output=T>, T> Parser for PrecedenceParser<'a, A, T> {
+    type Output = T;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| self.parse_expr(r, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::parse::Parser;
+    use crate::parse::combinators::*;
+    use crate::parse::precedence::{Assoc, PrecedenceParser};
+    use crate::parse::std_parsers::*;
+
+    // Parses arithmetic into a fully parenthesized string, which shows how operators were grouped.
+    fn grouping(input: &str) -> Result<String, String> {
+        let atom = non_neg_decimal::<u32>.map(|n| n.to_string());
+        PrecedenceParser::new(atom)
+            .prefix("-", 5, |a| format!("(-{})", a))
+            .postfix("!", 6, |a| format!("({}!)", a))
+            .infix("==", 0, Assoc::None, |a, b| format!("({} == {})", a, b))
+            .infix("+", 1, Assoc::Left, |a, b| format!("({} + {})", a, b))
+            .infix("-", 1, Assoc::Left, |a, b| format!("({} - {})", a, b))
+            .infix("*", 2, Assoc::Left, |a, b| format!("({} * {})", a, b))
+            .infix("^", 3, Assoc::Right, |a, b| format!("({} ^ {})", a, b))
+            .parse_to_end(&mut Cursor::new(input.as_bytes()))
+            .map_err(|error| error.reason())
+    }
+
+    #[test]
+    fn higher_precedence_binds_tighter() {
+        assert_eq!(grouping("1+2*3-4").unwrap(), "((1 + (2 * 3)) - 4)");
+        assert_eq!(grouping("-2^3!").unwrap(), "((-2) ^ (3!))");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(grouping("1-2-3").unwrap(), "((1 - 2) - 3)");
+        assert_eq!(grouping("2^3^4").unwrap(), "(2 ^ (3 ^ 4))");
+        assert_eq!(grouping("1+2==3").unwrap(), "((1 + 2) == 3)");
+    }
+
+    #[test]
+    fn non_associative_operators_cannot_be_chained() {
+        assert_eq!(grouping("1==2==3").unwrap_err(), "non-associative operators cannot be chained");
+    }
+}
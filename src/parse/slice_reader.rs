@@ -0,0 +1,143 @@
+use std::cmp;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::parse::{ParseError, Parser, ParseResult, ReadSeek, Span};
+
+// Reader over an in-memory `&str` or `&[u8]`. Reading, peeking, and seeking are just index operations, and since the
+// whole input is available, anything a parser consumed can be handed back as a slice borrowed from the input instead
+// of being copied (see `recognize`).
+#[derive(Debug, Clone)]
+pub struct SliceReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        SliceReader { input, pos: 0 }
+    }
+
+    // Gets the part of the input covered by `span`.
+    pub fn slice(&self, span: Span) -> &'a [u8] {
+        let end = cmp::min(span.end as usize, self.input.len());
+        &self.input[cmp::min(span.start as usize, end)..end]
+    }
+
+    // Gets the input which hasn't been read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+
+    // Runs `parser`, returning the slice of input it consumed rather than its output.
+    pub fn recognize(&mut self, parser: &impl Parser) -> ParseResult<&'a [u8]> {
+        let start = self.pos as u64;
+        parser.parse(self)?;
+        Ok(self.slice(Span::new(start, self.pos as u64)))
+    }
+
+    // Like `recognize`, but for parsers which consume valid UTF-8.
+    pub fn recognize_str(&mut self, parser: &impl Parser) -> ParseResult<&'a str> {
+        let start = self.pos as u64;
+        let slice = self.recognize(parser)?;
+        std::str::from_utf8(slice).map_err(|_| {
+            ParseError::new(start, "consumed input is not valid UTF-8").with_span(Span::new(start, self.pos as u64))
+        })
+    }
+}
+
+impl<'a> From<&'a str> for SliceReader<'a> {
+    fn from(input: &'a str) -> Self {
+        SliceReader::new(input.as_bytes())
+    }
+}
+
+impl<'a> From<&'a [u8]> for SliceReader<'a> {
+    fn from(input: &'a [u8]) -> Self {
+        SliceReader::new(input)
+    }
+}
+
+impl<'a> Read for SliceReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = cmp::min(buf.len(), self.input.len() - self.pos);
+        buf[..read].copy_from_slice(&self.input[self.pos..self.pos + read]);
+        self.pos += read;
+        Ok(read)
+    }
+}
+
+impl<'a> Seek for SliceReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.input.len() as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position"));
+        }
+
+        // Seeking past the end stops at the end.
+        self.pos = cmp::min(new_pos as usize, self.input.len());
+        Ok(self.pos as u64)
+    }
+}
+
+impl<'a> ReadSeek for SliceReader<'a> {
+    fn offset(&mut self) -> ParseResult<u64> {
+        Ok(self.pos as u64)
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        self.pos = cmp::min(pos as usize, self.input.len());
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.input.get(self.pos).copied();
+        if byte.is_some() {
+            self.pos += 1;
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        Ok(self.input.get(self.pos).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use crate::parse::{ReadSeek, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::slice_reader::SliceReader;
+
+    #[test]
+    fn recognize_borrows_consumed_input() {
+        let input = String::from("abab!");
+        let mut reader = SliceReader::from(input.as_str());
+        let consumed = reader.recognize_str(&"ab".many()).unwrap();
+        assert_eq!(consumed, "abab");
+        assert_eq!(reader.rest(), b"!");
+        assert_eq!(reader.slice(Span::new(1, 3)), b"ba");
+    }
+
+    #[test]
+    fn failed_recognize_reports_parser_error() {
+        let mut reader = SliceReader::from("x");
+        assert_eq!(reader.recognize(&"ab").unwrap_err().expected, ["'ab'"]);
+    }
+
+    #[test]
+    fn seeking_is_clamped_to_input() {
+        let mut reader = SliceReader::from("abc");
+        assert_eq!(reader.seek(SeekFrom::End(5)).unwrap(), 3);
+        assert!(reader.seek(SeekFrom::Current(-4)).is_err());
+        reader.seek_to(1).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read_byte().unwrap(), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::parse::{ParseError, ParseResult};
+
+// Bookkeeping shared by all parsers running on the current thread.
+//
+// Combinators like `ManyParser` and `OptionalParser` succeed by swallowing an error from their inner parser, but that
+// error is often the most useful one to report if parsing fails later on; for `1+(2*)`, the problem is the missing
+// operand after the `*`, not the `+`. These errors are recorded here so that the one which got furthest into the input
+// can be reported instead.
+//
+// Memoized results (see `MemoParser`) are also stored here for the duration of a `session`, keyed by the type which
+// identifies the memoized rule, a number telling `MemoParser` and `LeftRecursiveParser` entries apart, and the position
+// it started parsing at. So are errors which were recovered from (see `RecoverParser`), so they can all be reported at
+// the end.
+pub type MemoKey = (TypeId, usize, u64);
+
+#[derive(Default)]
+struct Session {
+    memo: HashMap<MemoKey, Box<dyn Any>>,
+    recovered: Vec<ParseError>,
+}
+
+thread_local! {
+    static FURTHEST_FAILURES: RefCell<Vec<Option<ParseError>>> = const { RefCell::new(vec![]) };
+    static SESSIONS: RefCell<Vec<Session>> = const { RefCell::new(vec![]) };
+}
+
+// Records `error` as a candidate for the furthest failure of the innermost `track_failures` call. This does nothing
+// when called outside of one.
+pub fn record_failure(error: &ParseError) {
+    FURTHEST_FAILURES.with(|failures| {
+        if let Some(furthest) = failures.borrow_mut().last_mut() {
+            *furthest = match furthest.take() {
+                Some(current) => Some(current.merge(error.clone())),
+                None => Some(error.clone()),
+            };
+        }
+    })
+}
+
+// Runs `f`, returning its result along with the furthest failure recorded while it ran. Failures recorded inside `f`
+// are not seen by any enclosing `track_failures` call unless the caller records them again.
+pub fn track_failures<T>(f: impl FnOnce() -> T) -> (T, Option<ParseError>) {
+    FURTHEST_FAILURES.with(|failures| failures.borrow_mut().push(None));
+    let result = f();
+    let furthest = FURTHEST_FAILURES.with(|failures| failures.borrow_mut().pop().flatten());
+    (result, furthest)
+}
+
+// Runs `f`, replacing its error (if any) with the furthest failure recorded while it ran. Committed errors are left
+// alone, since anything recorded before the commit point belongs to alternatives which were abandoned.
+pub fn report_furthest<T>(f: impl FnOnce() -> ParseResult<T>) -> ParseResult<T> {
+    let (result, furthest) = track_failures(f);
+    result.map_err(|error| match furthest {
+        Some(furthest) if !error.committed => furthest.merge(error),
+        _ => error,
+    })
+}
+
+// Runs `f` as a complete parse of one input. Memoized results are kept until `f` returns, and any error is replaced by
+// the furthest failure as in `report_furthest`. If `f` recovered from any errors, the first one is returned even if it
+// succeeded; use `recovering_session` to get the partial result.
+pub fn session<T>(f: impl FnOnce() -> ParseResult<T>) -> ParseResult<T> {
+    let (result, recovered) = recovering_session(f);
+    match recovered.into_iter().next() {
+        Some(error) if result.is_ok() => Err(error),
+        _ => result,
+    }
+}
+
+// Like `session`, but also returns the errors recovered from during the parse.
+pub fn recovering_session<T>(f: impl FnOnce() -> ParseResult<T>) -> (ParseResult<T>, Vec<ParseError>) {
+    SESSIONS.with(|sessions| sessions.borrow_mut().push(Session::default()));
+    let result = report_furthest(f);
+    let session = SESSIONS.with(|sessions| sessions.borrow_mut().pop()).unwrap();
+    (result, session.recovered)
+}
+
+pub fn in_session() -> bool {
+    SESSIONS.with(|sessions| !sessions.borrow().is_empty())
+}
+
+// Gets the value memoized for `key` in the innermost session, if there is one.
+pub fn memo_get<T: Clone + 'static>(key: &MemoKey) -> Option<T> {
+    SESSIONS.with(|sessions| sessions.borrow().last()?.memo.get(key)?.downcast_ref::<T>().cloned())
+}
+
+// Memoizes `value` for `key` in the innermost session. This does nothing outside of a session, since there would be no
+// telling whether later parses are even on the same input.
+pub fn memo_insert<T: 'static>(key: MemoKey, value: T) {
+    SESSIONS.with(|sessions| {
+        if let Some(session) = sessions.borrow_mut().last_mut() {
+            session.memo.insert(key, Box::new(value));
+        }
+    })
+}
+
+// Records `error` as recovered from in the innermost session. An error with the same span as one already recorded is
+// ignored, since it comes from running the same parser at the same position again (like in a memoized parser).
+pub fn record_recovered(error: ParseError) {
+    SESSIONS.with(|sessions| {
+        if let Some(session) = sessions.borrow_mut().last_mut() {
+            if !session.recovered.iter().any(|recovered| recovered.span == error.span) {
+                session.recovered.push(error);
+            }
+        }
+    })
+}
+
+// Gets the number of errors recovered from so far in the innermost session.
+pub fn recovered_count() -> usize {
+    SESSIONS.with(|sessions| sessions.borrow().last().map_or(0, |session| session.recovered.len()))
+}
+
+// Gets the errors recovered from since there were `count` of them.
+pub fn recovered_since(count: usize) -> Vec<ParseError> {
+    SESSIONS.with(|sessions| sessions.borrow().last().map_or(vec![], |session| session.recovered[count..].to_vec()))
+}
+
+// Forgets errors recovered from since there were `count` of them. This is used when backtracking, since the input those
+// errors were found in will be parsed again.
+pub fn truncate_recovered(count: usize) {
+    SESSIONS.with(|sessions| {
+        if let Some(session) = sessions.borrow_mut().last_mut() {
+            session.recovered.truncate(count);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::parse::{ParseError, Parser, state};
+    use crate::parse::combinators::*;
+
+    #[test]
+    fn failures_are_only_seen_by_the_innermost_tracker() {
+        state::record_failure(&ParseError::expected(9, "ignored"));
+        let ((_, inner), outer) = state::track_failures(|| {
+            state::track_failures(|| {
+                state::record_failure(&ParseError::expected(1, "'a'"));
+                state::record_failure(&ParseError::expected(3, "'b'"));
+            })
+        });
+        assert_eq!(inner.unwrap().span.start, 3);
+        assert!(outer.is_none());
+    }
+
+    #[test]
+    fn or_merges_errors_of_both_alternatives() {
+        let error = "a".or("b").parse(&mut Cursor::new("c".as_bytes())).unwrap_err();
+        assert_eq!(error.expected, ["'a'", "'b'"]);
+    }
+
+    #[test]
+    fn parse_to_end_reports_error_swallowed_by_many() {
+        let error = "a".and("b").many().parse_to_end(&mut Cursor::new("abac".as_bytes())).unwrap_err();
+        assert_eq!(error.span.start, 3);
+        assert_eq!(error.expected, ["'b'"]);
+    }
+}
@@ -5,7 +5,7 @@ use std::str::FromStr;
 use num::Integer;
 
 use crate::parse;
-use crate::parse::{ParseError, Parser, ParseResult, ReadSeek};
+use crate::parse::{ParseError, Parser, ParseResult, ReadSeek, Span};
 use crate::parse::combinators::{AndParserExt, ManyParserExt, MapParserExt, OptionalParserExt};
 
 // Parses a sequence of bytes.
@@ -18,29 +18,151 @@ impl<'a> Parser for ByteSeqParser<'a> {
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
         parse::backtrack_on_fail(reader, |r| {
-            let mut buf = [0];
-            for b in self.bytes {
-                r.read_exact(&mut buf)?;
-                if *b != buf[0] {
-                    let error = match String::from_utf8(self.bytes.to_vec()) {
-                        Ok(string) => format!("expected '{}'", string),
-                        _ => format!("expected bytes {:?}", self.bytes),
-                    };
-                    return Err(ParseError::new(&error));
+            let start = r.offset()?;
+            for (i, b) in self.bytes.iter().enumerate() {
+                let byte = r.read_byte()?;
+                if byte == Some(*b) {
+                    continue;
                 }
+
+                // Report the whole character which didn't match, rather than just the byte where it differs.
+                let found = match byte {
+                    Some(_) => {
+                        let char_start = match parse::is_utf8_continuation(self.bytes[i]) {
+                            true => self.bytes[..i].iter().rposition(|b| !parse::is_utf8_continuation(*b)).unwrap_or(0),
+                            _ => i,
+                        };
+                        r.seek_to(start + char_start as u64)?;
+                        describe_bytes(&[&self.bytes[..char_start], &read_char_bytes(r)?[..]].concat())
+                    }
+                    _ => "eof".to_string(),
+                };
+                let span = Span::new(start, r.offset()?);
+                let error = ParseError::expected(start, &describe_bytes(self.bytes)).with_found(&found);
+                return Err(error.with_span(span));
             }
             Ok(self.bytes)
         })
     }
 }
 
-pub fn bytes(bytes: &[u8]) -> ByteSeqParser {
+// Reads the next character, returning its UTF-8 encoding. If it isn't valid UTF-8, a single byte is read instead.
+fn read_char_bytes(reader: &mut impl ReadSeek) -> ParseResult<Vec<u8>> {
+    match reader.read_char() {
+        Ok(char) => Ok(char.map_or(vec![], |char| char.to_string().into_bytes())),
+        _ => Ok(reader.read_byte()?.into_iter().collect()),
+    }
+}
+
+// Describes `bytes` as a quoted string if they are valid UTF-8, and as a byte list otherwise.
+pub fn describe_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(string) => format!("'{}'", string.escape_debug()),
+        _ => format!("bytes {:?}", bytes),
+    }
+}
+
+pub fn bytes(bytes: &[u8]) -> ByteSeqParser<'_> {
     ByteSeqParser { bytes }
 }
 
-// Parses a string.
-pub fn string(string: &str) -> impl Parser<Output=String> + '_ {
-    bytes(string.as_bytes()).map(|b| String::from_utf8_lossy(b).to_string())
+// Parses a string. The result borrows from `string` rather than allocating, since it must be identical anyway.
+pub fn string(string: &str) -> impl Parser<Output=&str> + '_ {
+    bytes(string.as_bytes()).map(move |_| string)
+}
+
+// Parses a single character for which `predicate` returns true. `expected` describes the characters accepted, for error
+// messages.
+pub struct SatisfyParser<F: Fn(char) -> bool> {
+    predicate: F,
+    expected: Vec<String>,
+}
+
+impl<F: Fn(char) -> bool> Parser for SatisfyParser<F> {
+    type Output = char;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let pos = r.offset()?;
+            match r.read_char()? {
+                Some(char) if (self.predicate)(char) => Ok(char),
+                char => {
+                    let found = char.map_or("eof".to_string(), describe_char);
+                    let error = ParseError::expected(pos, "").with_expected(self.expected.clone());
+                    Err(error.with_found(&found).with_span(Span::new(pos, r.offset()?)))
+                }
+            }
+        })
+    }
+}
+
+impl<F: Fn(char) -> bool> SatisfyParser<F> {
+    // Replaces the description of the characters accepted.
+    pub fn expecting(self, expected: &str) -> Self {
+        SatisfyParser { expected: vec![expected.to_string()], ..self }
+    }
+}
+
+fn describe_char(char: char) -> String {
+    format!("{:?}", char)
+}
+
+// Parses a character for which `predicate` returns true. Use `expecting` to describe the characters for error messages.
+pub fn satisfy<F: Fn(char) -> bool>(predicate: F) -> SatisfyParser<F> {
+    SatisfyParser { predicate, expected: vec!["matching character".to_string()] }
+}
+
+// Parses any of the characters in `chars`.
+pub fn one_of(chars: &str) -> impl Parser<Output=char> {
+    let expected = chars.chars().map(describe_char).collect();
+    let chars = chars.chars().collect::<Vec<_>>();
+    SatisfyParser { predicate: move |char| chars.contains(&char), expected }
+}
+
+// Parses any character not in `chars`.
+pub fn none_of(chars: &str) -> impl Parser<Output=char> {
+    let described = chars.chars().map(describe_char).collect::<Vec<_>>();
+    let chars = chars.chars().collect::<Vec<_>>();
+    let expected = match described.len() {
+        0 => "any character".to_string(),
+        _ => format!("any character except {}", parse::join_alternatives(&described)),
+    };
+    satisfy(move |char| !chars.contains(&char)).expecting(&expected)
+}
+
+// Parses a character between `start` and `end`, inclusive.
+pub fn char_range(start: char, end: char) -> impl Parser<Output=char> {
+    let expected = format!("character from {} to {}", describe_char(start), describe_char(end));
+    satisfy(move |char| (start..=end).contains(&char)).expecting(&expected)
+}
+
+pub fn any_char() -> impl Parser<Output=char> {
+    satisfy(|_| true).expecting("any character")
+}
+
+// Parses an alphabetic character, which includes non-ASCII letters like `é` (see `char::is_alphabetic`).
+pub fn alpha() -> impl Parser<Output=char> {
+    satisfy(char::is_alphabetic).expecting("letter")
+}
+
+// Parses an alphabetic or numeric character (see `char::is_alphanumeric`).
+pub fn alphanumeric() -> impl Parser<Output=char> {
+    satisfy(char::is_alphanumeric).expecting("letter or digit")
+}
+
+// Parses an ASCII decimal digit.
+pub fn digit() -> impl Parser<Output=char> {
+    satisfy(|char| char.is_ascii_digit()).expecting("digit")
+}
+
+// Parses an ASCII hexadecimal digit, in either case.
+pub fn hex_digit() -> impl Parser<Output=char> {
+    satisfy(|char| char.is_ascii_hexdigit()).expecting("hexadecimal digit")
+}
+
+// Parses a line feed.
+pub fn newline() -> impl Parser<Output=char> {
+    satisfy(|char| char == '\n').expecting("newline")
 }
 
 // Parses and discards any amount of whitespace.
@@ -68,20 +190,23 @@ impl<I: Integer + FromStr> Parser for NonNegDecimalParser<I> {
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
         parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
             let mut string = String::new();
-            let mut buf = [0];
 
-            while r.read(&mut buf)? > 0 {
-                if !buf[0].is_ascii_digit() {
-                    parse::seek_back_one(r)?;
+            while let Some(byte) = r.peek_byte()? {
+                if !byte.is_ascii_digit() {
                     break;
                 }
-                string.push(buf[0] as char);
+                string.push(byte as char);
+                r.read_byte()?;
             }
 
+            if string.is_empty() {
+                return Err(ParseError::expected(start, "decimal integer"));
+            }
             string.parse::<I>().map_err(|_| {
-                let message = format!("decimal integer literal too large: {}", string);
-                ParseError::new(&message)
+                let span = Span::new(start, start + string.len() as u64);
+                ParseError::new(start, &format!("decimal integer literal too large: {}", string)).with_span(span)
             })
         })
     }
@@ -97,6 +222,147 @@ pub fn decimal<I: Integer + FromStr + Neg<Output=I>>() -> impl Parser<Output=I>
     sign.and(non_neg_decimal).map(|(sign_fn, n)| sign_fn(n))
 }
 
+// Parses a quoted string literal, returning its contents with escape sequences replaced. Supported escapes are `\n`,
+// `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, `\x` followed by two hex digits (up to `\x7f`), and `\u{...}` with up to six
+// hex digits. If `raw` is set, raw strings like `r"C:\path"` or `r#"say "hi""#` are accepted too, where escapes aren't
+// processed and the literal ends at a quote followed by as many `#`s as it started with.
+//
+// Errors after the opening quote (like a bad escape or a missing closing quote) are committed (see `CutParser`), since
+// there's nothing else the input could be.
+pub struct StringLiteralParser {
+    quotes: Vec<char>,
+    escapes: bool,
+    raw: bool,
+}
+
+impl StringLiteralParser {
+    // Sets which characters can be used to quote the literal. The closing quote must match the opening one.
+    pub fn with_quotes(self, quotes: &str) -> Self {
+        StringLiteralParser { quotes: quotes.chars().collect(), ..self }
+    }
+
+    pub fn with_escapes(self, escapes: bool) -> Self {
+        StringLiteralParser { escapes, ..self }
+    }
+
+    pub fn with_raw(self, raw: bool) -> Self {
+        StringLiteralParser { raw, ..self }
+    }
+
+    // Reads the `r` and `#`s starting a raw string, returning the number of `#`s, or `None` if it isn't one.
+    fn read_raw_start(&self, reader: &mut impl ReadSeek) -> ParseResult<Option<usize>> {
+        if !self.raw || reader.peek_byte()? != Some(b'r') {
+            return Ok(None);
+        }
+        reader.read_byte()?;
+        let mut hashes = 0;
+        while reader.peek_byte()? == Some(b'#') {
+            reader.read_byte()?;
+            hashes += 1;
+        }
+        Ok(Some(hashes))
+    }
+
+    // Reads the rest of an escape sequence whose backslash was at `start`.
+    fn read_escape(&self, reader: &mut impl ReadSeek, start: u64) -> ParseResult<char> {
+        let invalid = |reader: &mut dyn ReadSeek, message: &str| {
+            let span = Span::new(start, reader.offset()?);
+            Err(ParseError::new(start, message).with_span(span).with_committed(true))
+        };
+        let char = match reader.read_char()? {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(char @ '\\') | Some(char @ '"') | Some(char @ '\'') => char,
+            Some('x') => {
+                let mut digits = String::new();
+                while digits.len() < 2 && reader.peek_char()?.is_some_and(|char| char.is_ascii_hexdigit()) {
+                    digits.extend(reader.read_char()?);
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 && byte <= 0x7f => byte as char,
+                    Ok(_) if digits.len() == 2 => return invalid(reader, "hex escape must be at most \\x7f"),
+                    _ => return invalid(reader, "hex escape must have two hex digits"),
+                }
+            }
+            Some('u') => {
+                if reader.peek_byte()? != Some(b'{') {
+                    return invalid(reader, "unicode escape must be like \\u{7fff}");
+                }
+                reader.read_byte()?;
+                let mut digits = String::new();
+                loop {
+                    match reader.read_char()? {
+                        Some('}') => break,
+                        Some(char) if char.is_ascii_hexdigit() && digits.len() < 6 => digits.push(char),
+                        _ => return invalid(reader, "unicode escape must be like \\u{7fff}"),
+                    }
+                }
+                let code = u32::from_str_radix(&digits, 16).ok();
+                match code.and_then(std::char::from_u32) {
+                    Some(char) => char,
+                    _ => return invalid(reader, "invalid unicode escape"),
+                }
+            }
+            Some(_) => return invalid(reader, "unknown escape sequence"),
+            None => return invalid(reader, "unterminated escape sequence"),
+        };
+        Ok(char)
+    }
+}
+
+impl Parser for StringLiteralParser {
+    type Output = String;
+
+    fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
+        parse::backtrack_on_fail(reader, |r| {
+            let start = r.offset()?;
+            let raw_hashes = self.read_raw_start(r)?;
+            let quote = match r.read_char()? {
+                Some(char) if self.quotes.contains(&char) => char,
+                char => {
+                    let found = char.map_or("eof".to_string(), describe_char);
+                    return Err(ParseError::expected(start, "string literal").with_found(&found));
+                }
+            };
+
+            let mut value = String::new();
+            loop {
+                let pos = r.offset()?;
+                match r.read_char()? {
+                    Some(char) if char == quote => {
+                        // A raw string only ends if the quote is followed by enough `#`s.
+                        let hashes = raw_hashes.unwrap_or(0);
+                        let mut closing = 0;
+                        while closing < hashes && r.peek_byte()? == Some(b'#') {
+                            r.read_byte()?;
+                            closing += 1;
+                        }
+                        if closing == hashes {
+                            return Ok(value);
+                        }
+                        value.push(char);
+                        value.extend(std::iter::repeat_n('#', closing));
+                    }
+                    Some('\\') if self.escapes && raw_hashes.is_none() => value.push(self.read_escape(r, pos)?),
+                    Some(char) => value.push(char),
+                    None => {
+                        let error = ParseError::new(start, "unterminated string literal")
+                            .with_span(Span::new(start, start + 1))
+                            .with_label(Span::at(pos), "input ends here");
+                        return Err(error.with_committed(true));
+                    }
+                }
+            }
+        })
+    }
+}
+
+pub fn string_literal() -> StringLiteralParser {
+    StringLiteralParser { quotes: vec!['"'], escapes: true, raw: false }
+}
+
 // Parses an EOF error.
 pub struct EofParser;
 
@@ -105,11 +371,13 @@ impl Parser for EofParser {
 
     fn parse(&self, reader: &mut impl ReadSeek) -> ParseResult<Self::Output> {
         parse::backtrack_on_fail(reader, |r| {
-            let mut buf = [0];
-            match r.read(&mut buf) {
-                Ok(0) => Ok(()),
-                Ok(_) => Err(ParseError::new(&format!("unexpected '{}'", buf[0] as char))),
-                _ => Err(ParseError::new("expected eof"))
+            let pos = r.offset()?;
+            match r.peek_byte()? {
+                None => Ok(()),
+                Some(_) => {
+                    let error = ParseError::expected(pos, "eof").with_found(&describe_bytes(&read_char_bytes(r)?));
+                    Err(error.with_span(Span::new(pos, r.offset()?)))
+                }
             }
         })
     }
@@ -118,3 +386,94 @@ impl Parser for EofParser {
 pub fn eof() -> EofParser {
     EofParser
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{Parser, ParseResult, Span};
+    use crate::parse::combinators::*;
+    use crate::parse::slice_reader::SliceReader;
+    use crate::parse::std_parsers::*;
+
+    #[test]
+    fn character_classes_accept_unicode() {
+        let word = alpha().then(alphanumeric().many()).parse(&mut SliceReader::from("é1ü2 ")).unwrap();
+        assert_eq!(word, ['1', 'ü', '2']);
+        assert_eq!(hex_digit().many().parse(&mut SliceReader::from("fA9g")).unwrap(), ['f', 'A', '9']);
+        assert_eq!(char_range('α', 'ω').parse(&mut SliceReader::from("λ")).unwrap(), 'λ');
+    }
+
+    #[test]
+    fn character_class_errors_describe_what_was_accepted() {
+        let error = one_of("+-").parse(&mut SliceReader::from("*")).unwrap_err();
+        assert_eq!(error.reason(), "expected '+' or '-', found '*'");
+        let error = none_of("\"\\").parse(&mut SliceReader::from("\"")).unwrap_err();
+        assert_eq!(error.reason(), "expected any character except '\"' or '\\\\', found '\"'");
+        let error = satisfy(|char| char == 'e').expecting("exponent").parse(&mut SliceReader::from("")).unwrap_err();
+        assert_eq!(error.reason(), "expected exponent, found eof");
+    }
+
+    #[test]
+    fn failed_character_class_consumes_nothing() {
+        let mut reader = SliceReader::from("ü");
+        assert!(satisfy(char::is_numeric).parse(&mut reader).is_err());
+        assert_eq!(any_char().parse(&mut reader).unwrap(), 'ü');
+    }
+
+    #[test]
+    fn spaces_skips_only_spaces() {
+        let mut reader = SliceReader::from("  \tx");
+        spaces().parse(&mut reader).unwrap();
+        assert_eq!(reader.rest(), b"\tx");
+    }
+
+    fn literal(parser: &StringLiteralParser, input: &str) -> ParseResult<String> {
+        parser.parse(&mut SliceReader::from(input))
+    }
+
+    #[test]
+    fn string_literal_escapes() {
+        let parser = string_literal();
+        assert_eq!(literal(&parser, r#""a\n\t\\\"\x41\u{e9}\u{1F600}""#).unwrap(), "a\n\t\\\"Aé😀");
+        assert_eq!(literal(&parser, r#""it's""#).unwrap(), "it's");
+        assert_eq!(literal(&parser.with_escapes(false), r#""a\n""#).unwrap(), "a\\n");
+    }
+
+    #[test]
+    fn bad_escapes_are_committed_errors() {
+        let parser = string_literal();
+        for (input, message) in [
+            (r#""\q""#, "unknown escape sequence"),
+            (r#""\x80""#, "hex escape must be at most \\x7f"),
+            (r#""\x4""#, "hex escape must have two hex digits"),
+            (r#""\u{d800}""#, "invalid unicode escape"),
+            (r#""\u{1234567}""#, "unicode escape must be like \\u{7fff}"),
+        ] {
+            let error = literal(&parser, input).unwrap_err();
+            assert_eq!((error.reason().as_str(), error.span.start, error.committed), (message, 1, true));
+        }
+    }
+
+    #[test]
+    fn unterminated_string_literal_points_at_both_ends() {
+        let error = literal(&string_literal(), r#""abc"#).unwrap_err();
+        assert_eq!(error.reason(), "unterminated string literal");
+        assert_eq!(error.span, Span::new(0, 1));
+        assert_eq!(error.labels[0].span, Span::at(4));
+        assert!(error.committed);
+    }
+
+    #[test]
+    fn raw_string_literals() {
+        let parser = string_literal().with_raw(true);
+        assert_eq!(literal(&parser, r#"r"C:\path""#).unwrap(), r"C:\path");
+        assert_eq!(literal(&parser, r###"r##"say "#hi"#"##"###).unwrap(), r##"say "#hi"#"##);
+        assert!(!literal(&string_literal(), r#"r"a""#).unwrap_err().committed);
+    }
+
+    #[test]
+    fn string_literal_quotes() {
+        let parser = string_literal().with_quotes("'\"");
+        assert_eq!(literal(&parser, "'a\"b'").unwrap(), "a\"b");
+        assert!(literal(&parser, "'a\"").is_err());
+    }
+}
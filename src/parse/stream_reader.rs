@@ -0,0 +1,221 @@
+use std::cmp;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::parse::{ParseError, ParseResult, ReadSeek};
+
+// Makes a reader which can only be read forward (like stdin or a pipe) usable by parsers, by buffering its input so
+// that parsers can seek back into it. Only input which can still be reached by backtracking is kept: a parser which may
+// seek back to a position marks it first (see `ReadSeek::mark`), so anything before the earliest marked position (or
+// the current position, if nothing is marked) is dropped when more input is read.
+//
+// Note that a parser like `many` can backtrack to where it started until it finishes, so to keep memory use bounded on
+// a long stream, parse one item at a time (calling `parse` in a loop) rather than the whole stream at once.
+pub struct StreamReader<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+
+    // The position of the first byte in `buf`.
+    buf_start: u64,
+    pos: u64,
+    marks: Vec<u64>,
+}
+
+// How much to read from the underlying reader at once.
+const CHUNK_SIZE: usize = 8192;
+
+impl<R: Read> StreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        StreamReader { reader, buf: vec![], buf_start: 0, pos: 0, marks: vec![] }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Gets the position just after the last byte in `buf`.
+    fn buf_end(&self) -> u64 {
+        self.buf_start + self.buf.len() as u64
+    }
+
+    // Reads more input into `buf`, returning the number of bytes read (which is 0 at eof).
+    fn fill(&mut self) -> io::Result<usize> {
+        self.discard_unreachable();
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK_SIZE, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buf.truncate(len + *read.as_ref().unwrap_or(&0));
+        read
+    }
+
+    // Drops input which can't be reached anymore. This only happens once there's enough of it, so that the rest of the
+    // buffer isn't moved for every chunk read.
+    fn discard_unreachable(&mut self) {
+        let keep_from = self.marks.iter().copied().fold(self.pos, cmp::min);
+        let unreachable = (keep_from - self.buf_start) as usize;
+        if unreachable >= CHUNK_SIZE && unreachable >= self.buf.len() / 2 {
+            self.buf.drain(..unreachable);
+            self.buf_start = keep_from;
+        }
+    }
+
+    // Moves to `pos`, reading up to it if it hasn't been read yet. The position is left unchanged if this fails, so the
+    // input from the current position on is kept until the move succeeds.
+    fn move_to(&mut self, pos: i64) -> io::Result<u64> {
+        if pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position"));
+        }
+        let pos = pos as u64;
+        if pos < self.buf_start {
+            let message = "cannot seek to input which is no longer buffered";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        while pos > self.buf_end() {
+            if self.fill()? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "cannot seek past eof"));
+            }
+        }
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+impl<R: Read> Read for StreamReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf_end() && self.fill()? == 0 {
+            return Ok(0);
+        }
+        let start = (self.pos - self.buf_start) as usize;
+        let read = cmp::min(buf.len(), self.buf.len() - start);
+        buf[..read].copy_from_slice(&self.buf[start..start + read]);
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read> Seek for StreamReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(offset) => self.move_to(offset as i64),
+            SeekFrom::Current(offset) => self.move_to(self.pos as i64 + offset),
+            SeekFrom::End(offset) => {
+                // The end is only known after reading everything.
+                while self.fill()? > 0 {}
+                self.move_to(self.buf_end() as i64 + offset)
+            }
+        }
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+impl<R: Read> ReadSeek for StreamReader<R> {
+    fn offset(&mut self) -> ParseResult<u64> {
+        Ok(self.pos)
+    }
+
+    fn seek_to(&mut self, pos: u64) -> ParseResult<()> {
+        self.move_to(pos as i64).map(|_| ()).map_err(|e| ParseError::io(pos, e))
+    }
+
+    fn read_byte(&mut self) -> ParseResult<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if byte.is_some() {
+            self.pos += 1;
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> ParseResult<Option<u8>> {
+        if self.pos == self.buf_end() && self.fill().map_err(|e| ParseError::io(self.pos, e))? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.buf[(self.pos - self.buf_start) as usize]))
+    }
+
+    fn mark(&mut self, pos: u64) {
+        self.marks.push(pos);
+    }
+
+    fn release(&mut self, pos: u64) {
+        if let Some(i) = self.marks.iter().rposition(|mark| *mark == pos) {
+            self.marks.remove(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Seek, SeekFrom};
+
+    use crate::parse::{Parser, ReadSeek};
+    use crate::parse::combinators::*;
+    use crate::parse::stream_reader::{CHUNK_SIZE, StreamReader};
+
+    // A reader which can't seek and hands out a single byte per read.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let (first, rest) = match self.0.split_first() {
+                Some(split) => split,
+                _ => return Ok(0),
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn backtracks_into_buffered_input() {
+        let mut reader = StreamReader::new(Trickle(b"acab!"));
+        let parser = "ab".or("ac").many();
+        assert_eq!(parser.parse(&mut reader).unwrap(), ["ac", "ab"]);
+        assert_eq!(reader.read_byte().unwrap(), Some(b'!'));
+        assert_eq!(reader.read_byte().unwrap(), None);
+    }
+
+    #[test]
+    fn drops_input_which_cannot_be_reached() {
+        let input = "x;".repeat(CHUNK_SIZE * 4);
+        let mut reader = StreamReader::new(input.as_bytes());
+        while "x".with(";").parse(&mut reader).is_ok() {}
+        assert_eq!(reader.offset().unwrap(), input.len() as u64);
+        assert!(reader.buf.len() <= CHUNK_SIZE * 2);
+        assert!(reader.seek_to(0).is_err());
+    }
+
+    #[test]
+    fn keeps_marked_input() {
+        let input = "y".repeat(CHUNK_SIZE * 4);
+        let mut reader = StreamReader::new(input.as_bytes());
+        reader.mark(1);
+        while reader.read_byte().unwrap().is_some() {}
+        reader.seek_to(1).unwrap();
+        reader.release(1);
+        assert_eq!(reader.read_byte().unwrap(), Some(b'y'));
+    }
+
+    #[test]
+    fn failed_seeks_leave_the_position_unchanged() {
+        let mut reader = StreamReader::new(&[b'a'; 40000][..]);
+        reader.read_exact(&mut [0; 30000]).unwrap();
+
+        assert!(reader.seek(SeekFrom::Start(50000)).is_err());
+        assert_eq!(reader.stream_position().unwrap(), 30000);
+        assert!(reader.seek(SeekFrom::End(1)).is_err());
+        assert_eq!(reader.stream_position().unwrap(), 30000);
+        assert!(reader.seek(SeekFrom::Current(-40000)).is_err());
+        assert_eq!(reader.stream_position().unwrap(), 30000);
+
+        assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 29999);
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), 40000);
+    }
+}